@@ -0,0 +1,146 @@
+// Derive macro for the `FromJson` trait in `haversine-generator`'s `from_json` module.
+//
+// Field attributes:
+// - `#[json(rename = "name")]` reads the field from a different key.
+// - `#[json(default)]` uses `Default::default()` when the key is absent.
+// - `#[json(default = "path::to::function")]` calls that function when the key is absent.
+//
+// Container attributes:
+// - `#[json(from_array)]` also accepts an array holding the fields in declaration order.
+// - `#[json(crate = "path::to::from_json")]` names the module that provides `FromJson`, its
+//   helpers and `JsonValue`, for types outside `haversine-generator`. Defaults to
+//   `crate::from_json`.
+//
+// Every type parameter of the struct gets a `FromJson` bound on the generated impl.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, LitStr, parse_macro_input};
+
+enum WhenMissing {
+    Required,
+    UseDefault,
+    Call(syn::ExprPath),
+}
+
+struct FieldOptions {
+    key: String,
+    missing: WhenMissing,
+}
+
+#[proc_macro_derive(FromJson, attributes(json))]
+pub fn derive_from_json(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(&input, "FromJson can only be derived for structs"));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(&input, "FromJson needs a struct with named fields"));
+    };
+
+    let mut from_array = false;
+    let mut root: syn::Path = syn::parse_quote!(crate::from_json);
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("json")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("from_array") {
+                from_array = true;
+                Ok(())
+            } else if meta.path.is_ident("crate") {
+                root = meta.value()?.parse::<LitStr>()?.parse()?;
+                Ok(())
+            } else {
+                Err(meta.error("unknown json container attribute"))
+            }
+        })?;
+    }
+
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for param in input.generics.type_params() {
+        let ident = &param.ident;
+        where_clause.predicates.push(syn::parse_quote!(#ident: #root::FromJson));
+    }
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+    let mut object_fields = Vec::new();
+    let mut array_fields = Vec::new();
+    for (index, field) in fields.named.iter().enumerate() {
+        let ident = field.ident.as_ref().unwrap();
+        let options = field_options(field)?;
+        let key = &options.key;
+
+        let value = match &options.missing {
+            WhenMissing::Required => quote! { #root::field(object, #key)? },
+            WhenMissing::UseDefault => quote! {
+                #root::field_or_else(object, #key, ::std::default::Default::default)?
+            },
+            WhenMissing::Call(function) => quote! {
+                #root::field_or_else(object, #key, #function)?
+            },
+        };
+        object_fields.push(quote! { #ident: #value });
+        array_fields.push(quote! { #ident: #root::element(items, #index)? });
+    }
+
+    let array_branch = if from_array {
+        let count = fields.named.len();
+        quote! {
+            if let #root::JsonValue::Array(items) = value {
+                if items.len() != #count {
+                    return Err(#root::FromJsonError::new(format!(
+                        "expected an array of {} elements, found {}",
+                        #count,
+                        items.len()
+                    )));
+                }
+                return Ok(#name { #(#array_fields,)* });
+            }
+        }
+    } else {
+        TokenStream2::new()
+    };
+
+    Ok(quote! {
+        impl #impl_generics #root::FromJson for #name #type_generics #where_clause {
+            fn from_json(
+                value: &#root::JsonValue,
+            ) -> ::std::result::Result<Self, #root::FromJsonError> {
+                #array_branch
+                let object = #root::expect_object(value)?;
+                Ok(#name { #(#object_fields,)* })
+            }
+        }
+    })
+}
+
+fn field_options(field: &syn::Field) -> syn::Result<FieldOptions> {
+    let mut options = FieldOptions {
+        key: field.ident.as_ref().unwrap().to_string(),
+        missing: WhenMissing::Required,
+    };
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("json")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                options.key = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else if meta.path.is_ident("default") {
+                options.missing = if meta.input.peek(syn::Token![=]) {
+                    WhenMissing::Call(meta.value()?.parse::<LitStr>()?.parse()?)
+                } else {
+                    WhenMissing::UseDefault
+                };
+                Ok(())
+            } else {
+                Err(meta.error("unknown json field attribute"))
+            }
+        })?;
+    }
+    Ok(options)
+}
@@ -0,0 +1,120 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::from_json::FromJson;
+use crate::geodesy::Point;
+use crate::lexer::parse_file;
+use crate::parser::{JsonValue, parse_tokens};
+use crate::profile_block;
+
+#[derive(Debug, Clone)]
+pub struct NamedPoint {
+    pub name: String,
+    pub point: Point,
+}
+
+// Loads anchor points from JSON (`[{"name":..,"lat":..,"lng":..}]`, optionally wrapped in
+// `{"anchors": [...]}`) or from CSV lines of `name,lat,lng`.
+pub fn load_anchors(filename: &str) -> Result<Vec<NamedPoint>, Box<dyn std::error::Error>> {
+    profile_block!("LoadAnchors");
+    let is_json = Path::new(filename)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+
+    let anchors = if is_json {
+        load_anchors_json(filename)?
+    } else {
+        load_anchors_csv(filename)?
+    };
+
+    check_anchors(filename, &anchors)?;
+    Ok(anchors)
+}
+
+// Rejects anchor sets that cannot be sampled or written back to a metrics file line by line.
+pub fn check_anchors(source: &str, anchors: &[NamedPoint]) -> Result<(), Box<dyn std::error::Error>> {
+    if anchors.len() < 2 {
+        return Err(format!("{} must contain at least two anchor points", source).into());
+    }
+    for anchor in anchors {
+        let Point { lat, lng } = anchor.point;
+        if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lng) {
+            return Err(format!("{}: anchor '{}' has coordinates {}, {} outside the globe", source, anchor.name, lat, lng).into());
+        }
+        if anchor.name.contains(['\n', '\r']) {
+            return Err(format!("{}: anchor name {:?} contains a line break", source, anchor.name).into());
+        }
+    }
+    Ok(())
+}
+
+#[derive(FromJson)]
+struct AnchorEntry {
+    #[json(default)]
+    name: Option<String>,
+    lat: f64,
+    lng: f64,
+}
+
+#[derive(FromJson)]
+struct AnchorFile {
+    anchors: Vec<AnchorEntry>,
+}
+
+fn load_anchors_json(filename: &str) -> Result<Vec<NamedPoint>, Box<dyn std::error::Error>> {
+    let file = File::open(filename).map_err(|err| format!("Error reading {}: {}", filename, err))?;
+    let tokens = parse_file(file)
+        .map_err(|err| format!("Error parsing {}: {}", filename, err))?;
+    let json = parse_tokens(&tokens).map_err(|err| format!("Error parsing {}: {}", filename, err))?;
+
+    let entries = match &json {
+        JsonValue::Object(_) => AnchorFile::from_json(&json).map(|file| file.anchors),
+        _ => Vec::<AnchorEntry>::from_json(&json),
+    }
+    .map_err(|err| format!("Error reading {}: {}", filename, err))?;
+
+    let anchors = entries
+        .into_iter()
+        .enumerate()
+        .map(|(idx, entry)| NamedPoint {
+            name: entry.name.unwrap_or_else(|| format!("anchor-{}", idx)),
+            point: Point::new(entry.lat, entry.lng),
+        })
+        .collect();
+    Ok(anchors)
+}
+
+fn load_anchors_csv(filename: &str) -> Result<Vec<NamedPoint>, Box<dyn std::error::Error>> {
+    let file = File::open(filename).map_err(|err| format!("Error reading {}: {}", filename, err))?;
+    let reader = BufReader::new(file);
+    let mut anchors = Vec::new();
+    let mut first_row = true;
+
+    for (line_idx, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| format!("Error reading {}: {}", filename, err))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let is_first_row = std::mem::replace(&mut first_row, false);
+
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let [name, lat, lng] = fields[..] else {
+            return Err(format!("{}:{}: expected name,lat,lng", filename, line_idx + 1).into());
+        };
+        let (Ok(lat), Ok(lng)) = (lat.parse::<f64>(), lng.parse::<f64>()) else {
+            if is_first_row {
+                continue; // Header row
+            }
+            return Err(format!("{}:{}: invalid coordinates", filename, line_idx + 1).into());
+        };
+
+        anchors.push(NamedPoint {
+            name: name.to_string(),
+            point: Point::new(lat, lng),
+        });
+    }
+    Ok(anchors)
+}
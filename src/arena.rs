@@ -0,0 +1,606 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::mem::size_of;
+
+use crate::error::{ParseError, Position};
+use crate::events::{Event, EventParser};
+use crate::lexer::{Lexeme, TokenRef};
+use crate::object::JsonObject;
+use crate::parser::{DuplicateKeys, JsonValue, ParseOptions};
+use crate::profile_block;
+use crate::query::QueryNode;
+
+// Objects up to this size are searched linearly for duplicate keys while they are built.
+const INDEX_THRESHOLD: usize = 8;
+
+// A value in the arena. Containers refer to a contiguous run of `elements` or `members`, and
+// strings to a range of `strings`, so a node is 16 bytes and owns no allocation of its own.
+#[derive(Debug, Clone, Copy)]
+enum Node {
+    String { start: u32, len: u32 },
+    Number(f64),
+    Bool(bool),
+    Null,
+    Object { first: u32, len: u32 },
+    Array { first: u32, len: u32 },
+}
+
+// A whole document in a few flat vectors: nodes are stored children first with the root last,
+// and each distinct object key is stored once and referred to by number.
+#[derive(Debug, Default)]
+pub struct JsonArena {
+    nodes: Vec<Node>,
+    // Node numbers of array elements, each array's in one run.
+    elements: Vec<u32>,
+    // Key and node numbers of object members, each object's in one run.
+    members: Vec<(u32, u32)>,
+    // Text of every string value, back to back.
+    strings: String,
+    keys: Vec<String>,
+    key_ids: HashMap<String, u32>,
+    root: u32,
+}
+
+// Converts a length or position to a 32-bit arena offset, failing at `position` once the input
+// has outgrown them rather than wrapping.
+fn offset(value: usize, what: &str, position: impl FnOnce() -> Position) -> Result<u32, ParseError> {
+    u32::try_from(value).map_err(|_| {
+        ParseError::new(
+            position(),
+            format!("more than {} {}", u32::MAX, what),
+            "an input that fits 32-bit arena offsets",
+        )
+    })
+}
+
+impl JsonArena {
+    pub fn root(&self) -> ArenaValue<'_> {
+        ArenaValue {
+            arena: self,
+            node: self.root,
+        }
+    }
+
+    fn intern(&mut self, key: &str, position: impl FnOnce() -> Position) -> Result<u32, ParseError> {
+        if let Some(&id) = self.key_ids.get(key) {
+            return Ok(id);
+        }
+        let id = offset(self.keys.len(), "distinct keys", position)?;
+        self.keys.push(key.to_string());
+        self.key_ids.insert(key.to_string(), id);
+        Ok(id)
+    }
+
+    fn push_node(&mut self, node: Node, position: impl FnOnce() -> Position) -> Result<u32, ParseError> {
+        let id = offset(self.nodes.len(), "values", position)?;
+        self.nodes.push(node);
+        Ok(id)
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let key_bytes: usize = self.keys.iter().map(|key| key.capacity()).sum();
+        MemoryUsage {
+            nodes: self.nodes.len(),
+            node_bytes: self.nodes.capacity() * size_of::<Node>(),
+            link_bytes: self.elements.capacity() * size_of::<u32>()
+                + self.members.capacity() * size_of::<(u32, u32)>(),
+            string_bytes: self.strings.capacity(),
+            keys: self.keys.len(),
+            // Each key's text is held by both `keys` and `key_ids`.
+            key_bytes: 2 * key_bytes
+                + self.keys.capacity() * size_of::<String>()
+                + self.key_ids.capacity() * (size_of::<String>() + size_of::<u32>()),
+        }
+    }
+}
+
+// Heap bytes held by a `JsonArena`, by what they store.
+pub struct MemoryUsage {
+    pub nodes: usize,
+    pub node_bytes: usize,
+    pub link_bytes: usize,
+    pub string_bytes: usize,
+    pub keys: usize,
+    pub key_bytes: usize,
+}
+
+impl MemoryUsage {
+    pub fn total_bytes(&self) -> usize {
+        self.node_bytes + self.link_bytes + self.string_bytes + self.key_bytes
+    }
+}
+
+impl fmt::Display for MemoryUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mib = |bytes: usize| bytes as f64 / (1024.0 * 1024.0);
+        writeln!(f, "Arena nodes: {} ({:.3} MiB)", self.nodes, mib(self.node_bytes))?;
+        writeln!(f, "Arena links: {:.3} MiB", mib(self.link_bytes))?;
+        writeln!(f, "Arena strings: {:.3} MiB", mib(self.string_bytes))?;
+        writeln!(f, "Arena keys: {} distinct ({:.3} MiB)", self.keys, mib(self.key_bytes))?;
+        write!(f, "Arena total: {:.3} MiB", mib(self.total_bytes()))
+    }
+}
+
+// A borrowed view of one node, with the same accessors as `JsonValue`; `pointer` and `query`
+// come from `QueryNode`.
+#[derive(Clone, Copy)]
+pub struct ArenaValue<'a> {
+    arena: &'a JsonArena,
+    node: u32,
+}
+
+// The accessors mirror `JsonValue`'s for library-style use; the `query` command needs only some.
+impl<'a> ArenaValue<'a> {
+    fn node(self) -> Node {
+        self.arena.nodes[self.node as usize]
+    }
+
+    #[allow(dead_code, reason = "mirrors JsonValue::as_f64")]
+    pub fn as_f64(self) -> Option<f64> {
+        match self.node() {
+            Node::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> Option<&'a str> {
+        match self.node() {
+            Node::String { start, len } => Some(&self.arena.strings[start as usize..(start + len) as usize]),
+            _ => None,
+        }
+    }
+
+    #[allow(dead_code, reason = "mirrors JsonValue::as_bool")]
+    pub fn as_bool(self) -> Option<bool> {
+        match self.node() {
+            Node::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(self) -> Option<ArenaArray<'a>> {
+        match self.node() {
+            Node::Array { first, len } => Some(ArenaArray {
+                arena: self.arena,
+                elements: &self.arena.elements[first as usize..(first + len) as usize],
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(self) -> Option<ArenaObject<'a>> {
+        match self.node() {
+            Node::Object { first, len } => Some(ArenaObject {
+                arena: self.arena,
+                members: &self.arena.members[first as usize..(first + len) as usize],
+            }),
+            _ => None,
+        }
+    }
+
+    #[allow(dead_code, reason = "mirrors JsonValue::is_null")]
+    pub fn is_null(self) -> bool {
+        matches!(self.node(), Node::Null)
+    }
+
+    pub fn get(self, key: &str) -> Option<ArenaValue<'a>> {
+        self.as_object()?.get(key)
+    }
+
+    pub fn get_index(self, index: usize) -> Option<ArenaValue<'a>> {
+        self.as_array()?.get(index)
+    }
+
+    // Copies this node and everything under it into an owned tree.
+    pub fn to_json_value(self) -> JsonValue {
+        match self.node() {
+            Node::String { .. } => JsonValue::String(self.as_str().unwrap_or_default().to_string()),
+            Node::Number(n) => JsonValue::Number(n),
+            Node::Bool(b) => JsonValue::Bool(b),
+            Node::Null => JsonValue::Null,
+            Node::Array { .. } => JsonValue::Array(
+                self.as_array()
+                    .into_iter()
+                    .flat_map(|array| array.iter())
+                    .map(ArenaValue::to_json_value)
+                    .collect(),
+            ),
+            Node::Object { .. } => {
+                let mut map = JsonObject::new();
+                for (key, value) in self.as_object().into_iter().flat_map(|object| object.iter()) {
+                    map.push(key.to_string(), value.to_json_value());
+                }
+                JsonValue::Object(map)
+            }
+        }
+    }
+}
+
+impl<'a> QueryNode<'a> for ArenaValue<'a> {
+    fn is_array(self) -> bool {
+        matches!(self.node(), Node::Array { .. })
+    }
+
+    fn member(self, key: &str) -> Option<Self> {
+        self.get(key)
+    }
+
+    fn element(self, index: usize) -> Option<Self> {
+        self.get_index(index)
+    }
+
+    fn children(self) -> Box<dyn Iterator<Item = Self> + 'a> {
+        if let Some(array) = self.as_array() {
+            return Box::new(array.iter());
+        }
+        match self.as_object() {
+            Some(object) => Box::new(object.values()),
+            None => Box::new(std::iter::empty()),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ArenaArray<'a> {
+    arena: &'a JsonArena,
+    elements: &'a [u32],
+}
+
+impl<'a> ArenaArray<'a> {
+    #[allow(dead_code, reason = "mirrors len on the slice JsonValue::as_array returns")]
+    pub fn len(self) -> usize {
+        self.elements.len()
+    }
+
+    #[allow(dead_code, reason = "mirrors is_empty on the slice JsonValue::as_array returns")]
+    pub fn is_empty(self) -> bool {
+        self.elements.is_empty()
+    }
+
+    pub fn get(self, index: usize) -> Option<ArenaValue<'a>> {
+        let node = *self.elements.get(index)?;
+        Some(ArenaValue { arena: self.arena, node })
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = ArenaValue<'a>> + 'a {
+        let arena = self.arena;
+        self.elements.iter().map(move |&node| ArenaValue { arena, node })
+    }
+}
+
+// Members in insertion order. With the keep-all duplicate policy a key can appear more than
+// once; lookups then see the latest entry, as with `JsonObject`.
+#[derive(Clone, Copy)]
+pub struct ArenaObject<'a> {
+    arena: &'a JsonArena,
+    members: &'a [(u32, u32)],
+}
+
+impl<'a> ArenaObject<'a> {
+    #[allow(dead_code, reason = "mirrors JsonObject::is_empty")]
+    pub fn is_empty(self) -> bool {
+        self.members.is_empty()
+    }
+
+    pub fn get(self, key: &str) -> Option<ArenaValue<'a>> {
+        // A key that was never interned cannot be in any object.
+        let id = *self.arena.key_ids.get(key)?;
+        let &(_, node) = self.members.iter().rev().find(|&&(k, _)| k == id)?;
+        Some(ArenaValue { arena: self.arena, node })
+    }
+
+    #[allow(dead_code, reason = "mirrors JsonObject::contains_key")]
+    pub fn contains_key(self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = (&'a str, ArenaValue<'a>)> + 'a {
+        let arena = self.arena;
+        self.members
+            .iter()
+            .map(move |&(key, node)| (arena.keys[key as usize].as_str(), ArenaValue { arena, node }))
+    }
+
+    pub fn values(self) -> impl Iterator<Item = ArenaValue<'a>> + 'a {
+        self.iter().map(|(_, value)| value)
+    }
+}
+
+// An object or array whose children are still arriving; they collect at the end of the
+// builder's pending vectors from `start` on.
+struct Frame {
+    is_object: bool,
+    start: usize,
+    // Key to position in `pending_members`, built once the object outgrows a linear scan.
+    index: Option<HashMap<u32, usize>>,
+}
+
+// Builds a `JsonArena` from tokens with the same grammar as `Parser`, applying the same
+// duplicate key policies.
+pub struct ArenaBuilder {
+    duplicate_keys: DuplicateKeys,
+    events: EventParser,
+    arena: JsonArena,
+    frames: Vec<Frame>,
+    pending_elements: Vec<u32>,
+    pending_members: Vec<(u32, u32)>,
+    key_stack: Vec<u32>,
+}
+
+impl ArenaBuilder {
+    pub fn new(options: &ParseOptions) -> Self {
+        ArenaBuilder {
+            duplicate_keys: options.duplicate_keys,
+            events: EventParser::new(options),
+            arena: JsonArena::default(),
+            frames: Vec::new(),
+            pending_elements: Vec::new(),
+            pending_members: Vec::new(),
+            key_stack: Vec::new(),
+        }
+    }
+
+    // `position` is only evaluated when the token has to be reported in an error.
+    pub fn push_token(
+        &mut self,
+        token: TokenRef<'_>,
+        position: impl Fn() -> Position,
+    ) -> Result<(), ParseError> {
+        let Some(event) = self.events.push_token(token, &position)? else {
+            return Ok(());
+        };
+        match event {
+            Event::StartObject | Event::StartArray => {
+                let is_object = matches!(event, Event::StartObject);
+                self.frames.push(Frame {
+                    is_object,
+                    start: if is_object {
+                        self.pending_members.len()
+                    } else {
+                        self.pending_elements.len()
+                    },
+                    index: None,
+                });
+            }
+            Event::Key(key) => {
+                let id = self.arena.intern(&key, &position)?;
+                if self.duplicate_keys == DuplicateKeys::Error && self.find_member(id).is_some() {
+                    return Err(ParseError::new(
+                        position(),
+                        format!("duplicate key \"{}\"", key),
+                        "a key not already in the object",
+                    ));
+                }
+                self.key_stack.push(id);
+            }
+            Event::EndObject | Event::EndArray => self.close_container(&position)?,
+            Event::String(s) => {
+                let start = offset(self.arena.strings.len(), "bytes of string values", &position)?;
+                let end = offset(self.arena.strings.len() + s.len(), "bytes of string values", &position)?;
+                self.arena.strings.push_str(&s);
+                let node = self.arena.push_node(Node::String { start, len: end - start }, &position)?;
+                self.add_child(node);
+            }
+            Event::Number(n) => {
+                let node = self.arena.push_node(Node::Number(n), &position)?;
+                self.add_child(node);
+            }
+            Event::Bool(b) => {
+                let node = self.arena.push_node(Node::Bool(b), &position)?;
+                self.add_child(node);
+            }
+            Event::Null => {
+                let node = self.arena.push_node(Node::Null, &position)?;
+                self.add_child(node);
+            }
+        }
+        Ok(())
+    }
+
+    // Position in `pending_members` of the latest entry for `key` in the innermost object.
+    fn find_member(&mut self, key: u32) -> Option<usize> {
+        let frame = self.frames.last_mut()?;
+        if let Some(index) = &frame.index {
+            return index.get(&key).copied();
+        }
+        let members = &self.pending_members[frame.start..];
+        if members.len() > INDEX_THRESHOLD {
+            let index: HashMap<u32, usize> = members
+                .iter()
+                .enumerate()
+                .map(|(i, &(k, _))| (k, frame.start + i))
+                .collect();
+            let found = index.get(&key).copied();
+            frame.index = Some(index);
+            return found;
+        }
+        members.iter().rposition(|&(k, _)| k == key).map(|i| frame.start + i)
+    }
+
+    // Attaches a finished node to the innermost open container; with none open it is the root.
+    fn add_child(&mut self, node: u32) {
+        let Some(frame) = self.frames.last() else {
+            self.arena.root = node;
+            return;
+        };
+        if !frame.is_object {
+            self.pending_elements.push(node);
+            return;
+        }
+        let Some(key) = self.key_stack.pop() else {
+            return;
+        };
+        let existing = match self.duplicate_keys {
+            DuplicateKeys::FirstWins | DuplicateKeys::LastWins => self.find_member(key),
+            DuplicateKeys::Error | DuplicateKeys::KeepAll => None,
+        };
+        match (self.duplicate_keys, existing) {
+            (DuplicateKeys::FirstWins, Some(_)) => {}
+            (DuplicateKeys::LastWins, Some(position)) => self.pending_members[position].1 = node,
+            _ => {
+                let position = self.pending_members.len();
+                self.pending_members.push((key, node));
+                if let Some(index) = self.frames.last_mut().and_then(|frame| frame.index.as_mut()) {
+                    index.insert(key, position);
+                }
+            }
+        }
+    }
+
+    // Moves the children of the innermost container into the arena and adds its node.
+    fn close_container(&mut self, position: impl Fn() -> Position) -> Result<(), ParseError> {
+        let Some(frame) = self.frames.pop() else {
+            return Ok(());
+        };
+        let node = if frame.is_object {
+            let first = offset(self.arena.members.len(), "object members", &position)?;
+            self.arena.members.extend(self.pending_members.drain(frame.start..));
+            Node::Object {
+                first,
+                len: offset(self.arena.members.len(), "object members", &position)? - first,
+            }
+        } else {
+            let first = offset(self.arena.elements.len(), "array elements", &position)?;
+            self.arena.elements.extend(self.pending_elements.drain(frame.start..));
+            Node::Array {
+                first,
+                len: offset(self.arena.elements.len(), "array elements", &position)? - first,
+            }
+        };
+        let node = self.arena.push_node(node, &position)?;
+        self.add_child(node);
+        Ok(())
+    }
+
+    // `end` locates the end of the input for the error raised when the value is incomplete.
+    pub fn finish(mut self, end: impl FnOnce() -> Position) -> Result<JsonArena, ParseError> {
+        if !self.events.is_complete() {
+            return Err(ParseError::new(end(), "end of input", self.events.expected()));
+        }
+        self.arena.nodes.shrink_to_fit();
+        self.arena.elements.shrink_to_fit();
+        self.arena.members.shrink_to_fit();
+        self.arena.strings.shrink_to_fit();
+        Ok(self.arena)
+    }
+}
+
+// Reads tokens as the lexer produces them straight into an arena, so peak memory is the arena
+// plus the nesting state.
+pub fn parse_arena<I>(tokens: I, options: &ParseOptions) -> Result<JsonArena, ParseError>
+where
+    I: IntoIterator<Item = Result<Lexeme, ParseError>>,
+{
+    profile_block!("Parse arena");
+    let mut builder = ArenaBuilder::new(options);
+    let mut last_position = Position::start();
+
+    for lexeme in tokens {
+        let Lexeme { token, position } = lexeme?;
+        last_position = position;
+        builder.push_token(token.into_ref(), || position)?;
+    }
+
+    builder.finish(|| last_position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{TokenStream, parse_file_with};
+    use crate::parser::parse_tokens_with;
+
+    const POLICIES: [DuplicateKeys; 4] =
+        [DuplicateKeys::Error, DuplicateKeys::FirstWins, DuplicateKeys::LastWins, DuplicateKeys::KeepAll];
+
+    fn options(duplicate_keys: DuplicateKeys) -> ParseOptions {
+        ParseOptions { strict: true, duplicate_keys }
+    }
+
+    fn parse_both(input: &str, options: &ParseOptions) -> (Result<JsonArena, ParseError>, Result<JsonValue, ParseError>) {
+        let arena = parse_arena(TokenStream::new(input.as_bytes(), options), options);
+        let tree = parse_file_with(input.as_bytes(), options).and_then(|tokens| parse_tokens_with(&tokens, options));
+        (arena, tree)
+    }
+
+    // Every accessor of the arena view agrees with the tree, all the way down.
+    fn assert_same(node: ArenaValue, value: &JsonValue) {
+        assert_eq!(node.as_f64(), value.as_f64());
+        assert_eq!(node.as_bool(), value.as_bool());
+        assert_eq!(node.as_str(), value.as_str());
+        assert_eq!(node.is_null(), value.is_null());
+        assert_eq!(node.is_array(), value.as_array().is_some());
+        match (node.as_array(), value.as_array()) {
+            (Some(array), Some(values)) => {
+                assert_eq!((array.len(), array.is_empty()), (values.len(), values.is_empty()));
+                for (i, element) in values.iter().enumerate() {
+                    assert_same(array.get(i).unwrap(), element);
+                }
+                assert!(array.get(values.len()).is_none());
+            }
+            (array, values) => assert_eq!(array.is_some(), values.is_some()),
+        }
+        match (node.as_object(), value.as_object()) {
+            (Some(object), Some(members)) => {
+                assert_eq!(object.is_empty(), members.is_empty());
+                assert!(object.iter().map(|(key, _)| key).eq(members.iter().map(|(key, _)| key.as_str())));
+                for (key, _) in members.iter() {
+                    assert!(object.contains_key(key));
+                    assert_same(object.get(key).unwrap(), value.get(key).unwrap());
+                }
+                assert!(!object.contains_key("absent"));
+            }
+            (object, members) => assert_eq!(object.is_some(), members.is_some()),
+        }
+        assert_eq!(&node.to_json_value(), value);
+    }
+
+    const DOCUMENTS: [&str; 4] = [
+        r#"{"pairs": [{"x0": 1, "y0": 2, "x1": 3, "y1": 4}, [5, 6, 7, 8]], "name": "t\u00e9st", "ok": true, "none": null}"#,
+        r#"{"a": 1, "b": {"c": [1, {"a": 2}]}, "a": 3}"#,
+        r#"{"k0": 0, "k1": 1, "k2": 2, "k3": 3, "k4": 4, "k5": 5, "k6": 6, "k7": 7, "k8": 8, "k9": 9, "k2": 20, "k0": {"x": "y"}}"#,
+        r#"[[], {}, "", 0, -1.5e3, [[["deep"]]], {"": {"": null}}]"#,
+    ];
+
+    #[test]
+    fn arena_matches_the_tree_for_every_policy() {
+        for policy in POLICIES {
+            for input in DOCUMENTS {
+                match parse_both(input, &options(policy)) {
+                    (Ok(arena), Ok(tree)) => assert_same(arena.root(), &tree),
+                    (Err(arena), Err(tree)) => assert_eq!(arena.to_string(), tree.to_string(), "{policy} {input}"),
+                    (arena, tree) => panic!("{policy} {input}: arena {:?}, tree {:?}", arena.err(), tree.err()),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pointer_and_query_match_the_tree() {
+        let pointers = ["", "/a", "/b/c/1/a", "/k0/x", "/k2", "/k10", "/pairs/0/x0", "/pairs/1/3", "/0", "/5/0/0/0", "/6//", "/nope"];
+        let paths = ["$", "$.a", "$.b.c[*]", "$.pairs[*].x0", "$.pairs[1][*]", "$.*", "$[*]", "$[5][0][0][0]", "$.k0.x"];
+        for policy in POLICIES {
+            for input in DOCUMENTS {
+                let (Ok(arena), Ok(tree)) = parse_both(input, &options(policy)) else {
+                    continue;
+                };
+                for pointer in pointers {
+                    let from_arena = arena.root().pointer(pointer).map(ArenaValue::to_json_value);
+                    assert_eq!(from_arena.as_ref(), (&tree).pointer(pointer), "{policy} {input} {pointer}");
+                }
+                for path in paths {
+                    let from_arena: Vec<JsonValue> = arena.root().query(path).unwrap().map(ArenaValue::to_json_value).collect();
+                    let from_tree: Vec<&JsonValue> = (&tree).query(path).unwrap().collect();
+                    assert!(from_arena.iter().eq(from_tree.iter().copied()), "{policy} {input} {path}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn offsets_past_32_bits_are_errors() {
+        assert_eq!(offset(u32::MAX as usize, "values", Position::start).unwrap(), u32::MAX);
+        let err = offset(u32::MAX as usize + 1, "values", Position::start).unwrap_err();
+        assert_eq!(err.found, format!("more than {} values", u32::MAX));
+    }
+}
@@ -0,0 +1,107 @@
+use std::fmt;
+
+// Location of a byte in the input. `line` and `column` are 1-based; columns count bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub fn start() -> Self {
+        Position {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    // Works out the line and column of `offset` by scanning `input` from the start; meant for
+    // error paths of tokenizers that only track offsets.
+    pub fn locate(input: &[u8], offset: usize) -> Self {
+        let offset = offset.min(input.len());
+        let before = &input[..offset];
+        let line_start = before.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        Position {
+            offset,
+            line: before.iter().filter(|&&b| b == b'\n').count() + 1,
+            column: offset - line_start + 1,
+        }
+    }
+
+    pub fn advance(&mut self, byte: u8) {
+        self.offset += 1;
+        if byte == b'\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+    }
+
+    // Position of an earlier byte on the same line.
+    pub fn back_to(&self, offset: usize) -> Position {
+        Position {
+            offset,
+            line: self.line,
+            column: self.column - (self.offset - offset),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ParseError {
+    pub position: Position,
+    pub found: String,
+    pub expected: String,
+}
+
+impl ParseError {
+    pub fn new(position: Position, found: impl Into<String>, expected: impl Into<String>) -> Self {
+        ParseError {
+            position,
+            found: found.into(),
+            expected: expected.into(),
+        }
+    }
+
+    // Renders the offending line with a caret under the error column. `line_text` is the full
+    // text of line `self.position.line`.
+    pub fn snippet(&self, line_text: &str) -> String {
+        let line_number = self.position.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        let prefix_len = (self.position.column - 1).min(line_text.len());
+        let caret_column = String::from_utf8_lossy(&line_text.as_bytes()[..prefix_len])
+            .chars()
+            .count();
+
+        format!(
+            "{gutter} |\n{line_number} | {line}\n{gutter} | {pad}^ found {found}, expected {expected}",
+            line = line_text,
+            pad = " ".repeat(caret_column),
+            found = self.found,
+            expected = self.expected,
+        )
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {} (byte {}): found {}, expected {}",
+            self.position.line, self.position.column, self.position.offset, self.found, self.expected
+        )
+    }
+}
+
+// Errors bubble out of `main` through `Box<dyn Error>`, which prints them with `Debug`.
+impl fmt::Debug for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for ParseError {}
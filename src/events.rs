@@ -0,0 +1,344 @@
+use std::borrow::Cow;
+
+use crate::error::{ParseError, Position};
+use crate::lexer::{Lexeme, TokenRef};
+use crate::parser::{ParseOptions, StateItem};
+
+// One step through a JSON document, in the order its tokens appear. Keys and string values
+// borrow from the token when they can.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event<'a> {
+    StartObject,
+    EndObject,
+    StartArray,
+    EndArray,
+    Key(Cow<'a, str>),
+    String(Cow<'a, str>),
+    Number(f64),
+    Bool(bool),
+    Null,
+}
+
+impl Event<'_> {
+    // What a consumer found where it wanted something else, for error messages.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Event::StartObject => "an object",
+            Event::EndObject => "'}'",
+            Event::StartArray => "an array",
+            Event::EndArray => "']'",
+            Event::Key(_) => "a key",
+            Event::String(_) => "a string",
+            Event::Number(_) => "a number",
+            Event::Bool(_) => "a boolean",
+            Event::Null => "null",
+        }
+    }
+}
+
+// The grammar on its own: validates each token against the `StateItem` stack and reports what it
+// means as an `Event`, without keeping any of the values. `Parser` builds its tree from these.
+pub struct EventParser {
+    strict: bool,
+    state_stack: Vec<StateItem>,
+}
+
+impl EventParser {
+    pub fn new(options: &ParseOptions) -> Self {
+        EventParser {
+            strict: options.strict,
+            state_stack: vec![StateItem::ExpectingValue],
+        }
+    }
+
+    // Returns `None` for punctuation and, outside strict mode, for tokens that do not fit the
+    // grammar and are skipped. `position` is only evaluated for errors.
+    pub fn push_token<'a>(
+        &mut self,
+        token: TokenRef<'a>,
+        position: impl FnOnce() -> Position,
+    ) -> Result<Option<Event<'a>>, ParseError> {
+        let Some(current_state) = self.state_stack.last().copied() else {
+            if self.strict {
+                return Err(ParseError::new(position(), token.describe(), "end of input"));
+            }
+            // Anything after a complete root value is ignored.
+            return Ok(None);
+        };
+        let state_stack = &mut self.state_stack;
+        let event = match (&current_state, token) {
+            (
+                StateItem::ExpectingValue
+                | StateItem::ExpectingValueInObject
+                | StateItem::ExpectingValueInArray
+                | StateItem::ExpectingValueOrEndArray,
+                TokenRef::OpenBrace,
+            ) => {
+                state_stack.pop();
+                state_stack.push(StateItem::InObject);
+                state_stack.push(StateItem::ExpectingKeyOrEndObject);
+                Event::StartObject
+            }
+            (
+                StateItem::ExpectingKey | StateItem::ExpectingKeyOrEndObject,
+                TokenRef::StringContent(s),
+            ) => {
+                state_stack.pop();
+                state_stack.push(StateItem::ExpectingColon);
+                Event::Key(s)
+            }
+            (StateItem::ExpectingColon, TokenRef::Colon) => {
+                state_stack.pop();
+                state_stack.push(StateItem::ExpectingCommaOrEndObject);
+                state_stack.push(StateItem::ExpectingValueInObject);
+                return Ok(None);
+            }
+            (
+                StateItem::ExpectingValue
+                | StateItem::ExpectingValueInObject
+                | StateItem::ExpectingValueInArray
+                | StateItem::ExpectingValueOrEndArray,
+                token @ (TokenRef::StringContent(_)
+                | TokenRef::Number(_)
+                | TokenRef::Boolean(_)
+                | TokenRef::Null),
+            ) => {
+                state_stack.pop();
+                match token {
+                    TokenRef::StringContent(s) => Event::String(s),
+                    TokenRef::Number(n) => Event::Number(n),
+                    TokenRef::Boolean(b) => Event::Bool(b),
+                    _ => Event::Null,
+                }
+            }
+            (StateItem::ExpectingCommaOrEndObject, TokenRef::Comma) => {
+                state_stack.pop();
+                state_stack.push(StateItem::ExpectingKey);
+                return Ok(None);
+            }
+            (
+                StateItem::ExpectingCommaOrEndObject | StateItem::ExpectingKeyOrEndObject,
+                TokenRef::CloseBrace,
+            ) => {
+                state_stack.pop(); // ExpectingCommaOrEndObject or ExpectingKeyOrEndObject
+                state_stack.pop(); // InObject
+                Event::EndObject
+            }
+            (
+                StateItem::ExpectingValue
+                | StateItem::ExpectingValueInObject
+                | StateItem::ExpectingValueInArray
+                | StateItem::ExpectingValueOrEndArray,
+                TokenRef::OpenBracket,
+            ) => {
+                state_stack.pop();
+                state_stack.push(StateItem::InArray);
+                state_stack.push(StateItem::ExpectingCommaOrEndArray);
+                state_stack.push(StateItem::ExpectingValueOrEndArray);
+                Event::StartArray
+            }
+            (StateItem::ExpectingCommaOrEndArray, TokenRef::Comma) => {
+                state_stack.pop();
+                state_stack.push(StateItem::ExpectingCommaOrEndArray);
+                state_stack.push(StateItem::ExpectingValueInArray);
+                return Ok(None);
+            }
+            (StateItem::ExpectingValueOrEndArray, TokenRef::CloseBracket) => {
+                state_stack.pop(); // ExpectingValueOrEndArray
+                state_stack.pop(); // ExpectingCommaOrEndArray
+                state_stack.pop(); // InArray
+                Event::EndArray
+            }
+            (StateItem::ExpectingCommaOrEndArray, TokenRef::CloseBracket) => {
+                state_stack.pop(); // ExpectingCommaOrEndArray
+                state_stack.pop(); // InArray
+                Event::EndArray
+            }
+            // Outside strict mode a trailing comma may come before the closing bracket or brace.
+            (StateItem::ExpectingValueInArray, TokenRef::CloseBracket) if !self.strict => {
+                state_stack.pop(); // ExpectingValueInArray
+                state_stack.pop(); // ExpectingCommaOrEndArray
+                state_stack.pop(); // InArray
+                Event::EndArray
+            }
+            (StateItem::ExpectingKey, TokenRef::CloseBrace) if !self.strict => {
+                state_stack.pop(); // ExpectingKey
+                state_stack.pop(); // InObject
+                Event::EndObject
+            }
+            (_, token) => {
+                if self.strict {
+                    return Err(ParseError::new(
+                        position(),
+                        token.describe(),
+                        current_state.expected(),
+                    ));
+                }
+                return Ok(None);
+            }
+        };
+        Ok(Some(event))
+    }
+
+    // True once a whole root value has been seen.
+    pub fn is_complete(&self) -> bool {
+        self.state_stack.is_empty()
+    }
+
+    // Starts expecting another root value after a complete one.
+    pub fn start_next_document(&mut self) {
+        self.state_stack.push(StateItem::ExpectingValue);
+    }
+
+    // True when no part of a value has been seen since the start or `start_next_document`.
+    pub fn is_between_documents(&self) -> bool {
+        matches!(self.state_stack[..], [StateItem::ExpectingValue])
+    }
+
+    // What the grammar needs next to make progress, for error messages.
+    pub fn expected(&self) -> &'static str {
+        self.state_stack.last().map_or("a single root value", StateItem::expected)
+    }
+}
+
+// Events for the single root value in a stream of tokens, pulled one at a time so nothing but
+// the nesting state is kept.
+pub struct Events<I> {
+    tokens: I,
+    parser: EventParser,
+    last_position: Position,
+    done: bool,
+}
+
+impl<I> Iterator for Events<I>
+where
+    I: Iterator<Item = Result<Lexeme, ParseError>>,
+{
+    type Item = Result<Event<'static>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = loop {
+            let Some(lexeme) = self.tokens.next() else {
+                self.done = true;
+                if self.parser.is_complete() {
+                    return None;
+                }
+                return Some(Err(ParseError::new(
+                    self.last_position,
+                    "end of input",
+                    self.parser.expected(),
+                )));
+            };
+            let pushed = lexeme.and_then(|Lexeme { token, position }| {
+                self.last_position = position;
+                self.parser.push_token(token.into_ref(), || position)
+            });
+            match pushed {
+                Ok(Some(event)) => break Ok(event),
+                Ok(None) => {}
+                Err(err) => break Err(err),
+            }
+        };
+        self.done = result.is_err();
+        Some(result)
+    }
+}
+
+pub fn parse_events<I>(tokens: I, options: &ParseOptions) -> Events<I::IntoIter>
+where
+    I: IntoIterator<Item = Result<Lexeme, ParseError>>,
+{
+    Events {
+        tokens: tokens.into_iter(),
+        parser: EventParser::new(options),
+        last_position: Position::start(),
+        done: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::TokenStream;
+
+    fn events(input: &str, options: &ParseOptions) -> Result<Vec<Event<'static>>, ParseError> {
+        parse_events(TokenStream::new(input.as_bytes(), options), options).collect()
+    }
+
+    fn key(key: &str) -> Event<'static> {
+        Event::Key(Cow::Owned(key.to_string()))
+    }
+
+    #[test]
+    fn events_follow_document_order() {
+        let input = r#"{"b": [1, "two", true, null], "a": {"c": false}, "e": []}"#;
+        let expected = [
+            Event::StartObject,
+            key("b"),
+            Event::StartArray,
+            Event::Number(1.0),
+            Event::String(Cow::Borrowed("two")),
+            Event::Bool(true),
+            Event::Null,
+            Event::EndArray,
+            key("a"),
+            Event::StartObject,
+            key("c"),
+            Event::Bool(false),
+            Event::EndObject,
+            key("e"),
+            Event::StartArray,
+            Event::EndArray,
+            Event::EndObject,
+        ];
+        for options in [ParseOptions::default(), ParseOptions { strict: true, ..ParseOptions::default() }] {
+            assert_eq!(events(input, &options).unwrap(), expected);
+        }
+        assert_eq!(events("  -2.5 ", &ParseOptions::default()).unwrap(), [Event::Number(-2.5)]);
+    }
+
+    #[test]
+    fn strict_mode_reports_what_was_expected() {
+        let strict = ParseOptions { strict: true, ..ParseOptions::default() };
+        for (input, found, expected) in [
+            (r#"{"a" 1}"#, "number 1", "':'"),
+            (r#"{"a": 1,}"#, "'}'", "a string key"),
+            ("[1 2]", "number 2", "',' or ']'"),
+            ("[1] 2", "number 2", "end of input"),
+            ("[1, 2", "end of input", "',' or ']'"),
+            ("", "end of input", "a value"),
+        ] {
+            let err = events(input, &strict).unwrap_err();
+            assert_eq!((err.found.as_str(), err.expected.as_str()), (found, expected), "{input}");
+        }
+    }
+
+    #[test]
+    fn lenient_mode_skips_tokens_that_do_not_fit() {
+        let lenient = ParseOptions::default();
+        assert_eq!(
+            events(r#"[1 2, , 3] 4"#, &lenient).unwrap(),
+            [Event::StartArray, Event::Number(1.0), Event::Number(3.0), Event::EndArray]
+        );
+        assert_eq!(events("[1", &lenient).unwrap_err().expected, "',' or ']'");
+    }
+
+    #[test]
+    fn consecutive_documents() {
+        let mut parser = EventParser::new(&ParseOptions { strict: true, ..ParseOptions::default() });
+        assert!(parser.is_between_documents());
+        let mut seen = Vec::new();
+        for token in [TokenRef::OpenBracket, TokenRef::CloseBracket, TokenRef::Null] {
+            if parser.is_complete() {
+                parser.start_next_document();
+            }
+            seen.extend(parser.push_token(token, Position::start).unwrap());
+            assert!(!parser.is_between_documents());
+        }
+        assert!(parser.is_complete());
+        assert_eq!(seen, [Event::StartArray, Event::EndArray, Event::Null]);
+    }
+}
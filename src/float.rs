@@ -0,0 +1,312 @@
+use crate::float_table::{LARGEST_POWER_OF_FIVE, POWERS_OF_FIVE_128, SMALLEST_POWER_OF_FIVE};
+
+const MANTISSA_BITS: u32 = 52;
+const MINIMUM_EXPONENT: i32 = -1023;
+const INFINITE_POWER: i32 = 0x7FF;
+const MAX_DIGITS: usize = 19;
+const MAX_MANTISSA_FAST_PATH: u64 = 2 << MANTISSA_BITS;
+
+#[rustfmt::skip]
+const POWERS_OF_TEN: [f64; 23] = [
+    1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11,
+    1e12, 1e13, 1e14, 1e15, 1e16, 1e17, 1e18, 1e19, 1e20, 1e21, 1e22,
+];
+
+// A decimal number as `mantissa * 10^exponent`. `truncated` is set when digits beyond the first
+// 19 significant ones were non-zero and had to be dropped.
+struct Decimal {
+    negative: bool,
+    mantissa: u64,
+    exponent: i64,
+    truncated: bool,
+}
+
+// Biased binary exponent and explicit mantissa bits of an f64; `exponent` is -1 when the fast
+// algorithm could not decide the rounding.
+#[derive(PartialEq, Eq)]
+struct BinaryFloat {
+    mantissa: u64,
+    exponent: i32,
+}
+
+// Converts a decimal number straight from the input bytes, rounding correctly to nearest-even.
+// Accepts the same syntax as `str::parse::<f64>` for the characters the lexers collect: an
+// optional sign, digits with an optional fraction, and an optional exponent.
+pub fn parse_f64(bytes: &[u8]) -> Option<f64> {
+    let decimal = parse_decimal(bytes)?;
+    let value = if decimal.mantissa == 0 {
+        0.0
+    } else if let Some(value) = fast_path(&decimal) {
+        value
+    } else {
+        match eisel_lemire(&decimal) {
+            Some(binary) => f64::from_bits(binary.mantissa | (binary.exponent as u64) << MANTISSA_BITS),
+            // Rare enough (halfway cases with many digits) to leave to the standard library.
+            None => return std::str::from_utf8(bytes).ok()?.parse().ok(),
+        }
+    };
+    Some(if decimal.negative { -value } else { value })
+}
+
+fn parse_decimal(bytes: &[u8]) -> Option<Decimal> {
+    let (negative, bytes) = match bytes.split_first() {
+        Some((b'-', rest)) => (true, rest),
+        Some((b'+', rest)) => (false, rest),
+        _ => (false, bytes),
+    };
+
+    let mut decimal = Decimal {
+        negative,
+        mantissa: 0,
+        exponent: 0,
+        truncated: false,
+    };
+    let mut significant = 0;
+    let mut i = 0;
+
+    let integer_start = i;
+    while let Some(digit) = bytes.get(i).filter(|b| b.is_ascii_digit()) {
+        if significant < MAX_DIGITS {
+            decimal.mantissa = decimal.mantissa * 10 + u64::from(digit - b'0');
+            significant += usize::from(decimal.mantissa != 0);
+        } else {
+            decimal.truncated |= *digit != b'0';
+            decimal.exponent += 1;
+        }
+        i += 1;
+    }
+    let mut digit_count = i - integer_start;
+
+    if bytes.get(i) == Some(&b'.') {
+        i += 1;
+        let fraction_start = i;
+        while let Some(digit) = bytes.get(i).filter(|b| b.is_ascii_digit()) {
+            if significant < MAX_DIGITS {
+                decimal.mantissa = decimal.mantissa * 10 + u64::from(digit - b'0');
+                significant += usize::from(decimal.mantissa != 0);
+                decimal.exponent -= 1;
+            } else {
+                decimal.truncated |= *digit != b'0';
+            }
+            i += 1;
+        }
+        digit_count += i - fraction_start;
+    }
+    if digit_count == 0 {
+        return None;
+    }
+
+    if let Some(b'e' | b'E') = bytes.get(i) {
+        i += 1;
+        let exponent_negative = match bytes.get(i) {
+            Some(b'-') => {
+                i += 1;
+                true
+            }
+            Some(b'+') => {
+                i += 1;
+                false
+            }
+            _ => false,
+        };
+        let exponent_start = i;
+        let mut exponent: i64 = 0;
+        while let Some(digit) = bytes.get(i).filter(|b| b.is_ascii_digit()) {
+            // Anything this large is already zero or infinity.
+            if exponent < 0x10000 {
+                exponent = exponent * 10 + i64::from(digit - b'0');
+            }
+            i += 1;
+        }
+        if i == exponent_start {
+            return None;
+        }
+        decimal.exponent += if exponent_negative { -exponent } else { exponent };
+    }
+
+    (i == bytes.len()).then_some(decimal)
+}
+
+// Clinger's fast path: when the mantissa and the power of ten are both exact doubles, a single
+// correctly rounded multiply or divide gives the answer. Every number with at most 15 significant
+// digits and a small exponent takes this path.
+fn fast_path(decimal: &Decimal) -> Option<f64> {
+    if decimal.truncated || decimal.mantissa > MAX_MANTISSA_FAST_PATH {
+        return None;
+    }
+    match decimal.exponent {
+        exponent @ -22..=-1 => Some(decimal.mantissa as f64 / POWERS_OF_TEN[-exponent as usize]),
+        exponent @ 0..=22 => Some(decimal.mantissa as f64 * POWERS_OF_TEN[exponent as usize]),
+        // Move the excess exponent into the mantissa when it stays exact, e.g. 12e30.
+        exponent @ 23..=37 => {
+            let shifted = decimal.mantissa.checked_mul(10u64.pow(exponent as u32 - 22))?;
+            (shifted <= MAX_MANTISSA_FAST_PATH).then(|| shifted as f64 * POWERS_OF_TEN[22])
+        }
+        _ => None,
+    }
+}
+
+fn eisel_lemire(decimal: &Decimal) -> Option<BinaryFloat> {
+    let binary = compute_float(decimal.exponent, decimal.mantissa);
+    // Dropped digits put the true value between `mantissa` and `mantissa + 1`; the result is
+    // only certain if both ends round the same way.
+    if decimal.truncated && binary != compute_float(decimal.exponent, decimal.mantissa + 1) {
+        return None;
+    }
+    (binary.exponent >= 0).then_some(binary)
+}
+
+// Eisel-Lemire: multiplies the normalised mantissa by a 128-bit approximation of 10^q and reads
+// the rounded result off the top bits, giving up only when the truncated product is too close to
+// a halfway point to tell which way to round.
+fn compute_float(q: i64, mut w: u64) -> BinaryFloat {
+    let zero = BinaryFloat { mantissa: 0, exponent: 0 };
+    let infinity = BinaryFloat { mantissa: 0, exponent: INFINITE_POWER };
+    let undecided = BinaryFloat { mantissa: 0, exponent: -1 };
+
+    if w == 0 || q < SMALLEST_POWER_OF_FIVE {
+        return zero;
+    }
+    if q > LARGEST_POWER_OF_FIVE {
+        return infinity;
+    }
+
+    let leading_zeros = w.leading_zeros();
+    w <<= leading_zeros;
+    let (low, high) = product_approximation(q, w, MANTISSA_BITS + 3);
+    if low == u64::MAX && !(-27..=55).contains(&q) {
+        return undecided;
+    }
+
+    let upper_bit = (high >> 63) as i32;
+    let shift = upper_bit + 64 - MANTISSA_BITS as i32 - 3;
+    let mut mantissa = high >> shift;
+    let mut power2 = binary_power(q as i32) + upper_bit - leading_zeros as i32 - MINIMUM_EXPONENT;
+
+    if power2 <= 0 {
+        // Subnormal, or rounds up to the smallest normal number.
+        if -power2 + 1 >= 64 {
+            return zero;
+        }
+        mantissa >>= -power2 + 1;
+        mantissa += mantissa & 1;
+        mantissa >>= 1;
+        power2 = i32::from(mantissa >= 1 << MANTISSA_BITS);
+        return BinaryFloat { mantissa, exponent: power2 };
+    }
+
+    // Exactly halfway between two doubles: round to even rather than up.
+    if low <= 1 && (-4..=23).contains(&q) && mantissa & 3 == 1 && mantissa << shift == high {
+        mantissa &= !1;
+    }
+    mantissa += mantissa & 1;
+    mantissa >>= 1;
+    if mantissa >= 2 << MANTISSA_BITS {
+        mantissa = 1 << MANTISSA_BITS;
+        power2 += 1;
+    }
+    mantissa &= !(1 << MANTISSA_BITS);
+    if power2 >= INFINITE_POWER {
+        return infinity;
+    }
+    BinaryFloat { mantissa, exponent: power2 }
+}
+
+// floor(log2(10^q)) + 63, exact over the table's range.
+fn binary_power(q: i32) -> i32 {
+    (q.wrapping_mul(152_170 + 65536) >> 16) + 63
+}
+
+// Top 128 bits of `w * 5^q`, computing the second partial product only when the first leaves the
+// `precision` bits we need in doubt.
+fn product_approximation(q: i64, w: u64, precision: u32) -> (u64, u64) {
+    let mask = u64::MAX >> precision;
+    let (high5, low5) = POWERS_OF_FIVE_128[(q - SMALLEST_POWER_OF_FIVE) as usize];
+
+    let first = u128::from(w) * u128::from(high5);
+    let (mut low, mut high) = (first as u64, (first >> 64) as u64);
+    if high & mask == mask {
+        let second_high = ((u128::from(w) * u128::from(low5)) >> 64) as u64;
+        low = low.wrapping_add(second_high);
+        if second_high > low {
+            high += 1;
+        }
+    }
+    (low, high)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    fn assert_matches_std(text: &str) {
+        let expected: f64 = text.parse().unwrap();
+        let actual = parse_f64(text.as_bytes()).unwrap_or_else(|| panic!("rejected {text}"));
+        assert_eq!(actual.to_bits(), expected.to_bits(), "{text}: {actual:e} != {expected:e}");
+    }
+
+    #[test]
+    fn generator_coordinates() {
+        let mut rng = SmallRng::seed_from_u64(38);
+        for _ in 0..200_000 {
+            let value = rng.random_range(-180.0..=180.0f64);
+            assert_matches_std(&value.to_string());
+            assert_matches_std(&format!("{:.6}", value));
+        }
+    }
+
+    #[test]
+    fn random_bit_patterns() {
+        let mut rng = SmallRng::seed_from_u64(1038);
+        let mut checked = 0;
+        while checked < 200_000 {
+            let value = f64::from_bits(rng.random());
+            if !value.is_finite() {
+                continue;
+            }
+            assert_matches_std(&format!("{}", value));
+            assert_matches_std(&format!("{:e}", value));
+            checked += 1;
+        }
+    }
+
+    #[test]
+    fn truncated_and_rounded_digits() {
+        let mut rng = SmallRng::seed_from_u64(2038);
+        for _ in 0..100_000 {
+            let digits: String = (0..rng.random_range(1..40)).map(|_| char::from(b'0' + rng.random_range(0..10))).collect();
+            let exponent = rng.random_range(-330..310);
+            assert_matches_std(&format!("{}e{}", digits, exponent));
+            assert_matches_std(&format!("0.{}", digits));
+        }
+    }
+
+    #[test]
+    fn edge_cases() {
+        let cases = [
+            "0", "-0", "0.0", "-0.0e10", "0e-999", "1", "-1", "+1.5",
+            // Subnormals and the underflow boundary.
+            "4.9406564584124654e-324", "5e-324", "2.4703282292062327e-324", "2.4703282292062328e-324",
+            "2.2250738585072011e-308", "2.2250738585072014e-308", "1e-400",
+            // Largest finite values and overflow to infinity.
+            "1.7976931348623157e308", "1.7976931348623158e308", "1.7976931348623159e308", "1e309", "-1e400",
+            // Exactly representable values on and just past the fast-path limit.
+            "9007199254740992", "9007199254740993", "9007199254740993.0000000001", "1e22", "1e23",
+            "123456789e22", "0.1", "0.3",
+            // Mantissas of 19 digits and more.
+            "1234567890123456789", "12345678901234567890", "123456789012345678901234567890",
+            "0.1000000000000000055511151231257827021181583404541015625",
+            "0.1000000000000000055511151231257827021181583404541015624",
+            "0.1000000000000000055511151231257827021181583404541015626",
+            "9.999999999999999999999999999e-1", "00000000000000000000000001.5",
+            "1.00000000000000011102230246251565404236316680908203125",
+            "1.00000000000000011102230246251565404236316680908203124",
+            "1.00000000000000011102230246251565404236316680908203126",
+        ];
+        for case in cases {
+            assert_matches_std(case);
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use std::fmt;
+
+use crate::object::JsonObject;
+
+pub use crate::parser::JsonValue;
+pub use haversine_derive::FromJson;
+
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+// A value that did not have the expected shape, with the JSON path to it.
+pub struct FromJsonError {
+    // Innermost segment first; segments are added as the error travels up to the root.
+    path: Vec<PathSegment>,
+    message: String,
+}
+
+impl FromJsonError {
+    pub fn new(message: impl Into<String>) -> Self {
+        FromJsonError {
+            path: Vec::new(),
+            message: message.into(),
+        }
+    }
+
+    fn expected(expected: &str, found: &JsonValue) -> Self {
+        FromJsonError::new(format!("expected {}, found {}", expected, describe(found)))
+    }
+
+    pub fn at_key(mut self, key: &str) -> Self {
+        self.path.push(PathSegment::Key(key.to_string()));
+        self
+    }
+
+    pub fn at_index(mut self, index: usize) -> Self {
+        self.path.push(PathSegment::Index(index));
+        self
+    }
+
+    // The location in JSONPath notation, e.g. `$.pairs[3].x0`.
+    pub fn path(&self) -> String {
+        let mut path = String::from("$");
+        for segment in self.path.iter().rev() {
+            match segment {
+                PathSegment::Key(key) => {
+                    path.push('.');
+                    path.push_str(key);
+                }
+                PathSegment::Index(index) => path.push_str(&format!("[{}]", index)),
+            }
+        }
+        path
+    }
+}
+
+impl fmt::Display for FromJsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path(), self.message)
+    }
+}
+
+impl fmt::Debug for FromJsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for FromJsonError {}
+
+fn describe(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::String(_) => "a string",
+        JsonValue::Number(_) => "a number",
+        JsonValue::Bool(_) => "a boolean",
+        JsonValue::Null => "null",
+        JsonValue::Object(_) => "an object",
+        JsonValue::Array(_) => "an array",
+    }
+}
+
+// Conversion from a parsed document into a typed value. Usually derived with
+// `#[derive(FromJson)]`, which reads each field from the key of the same name.
+pub trait FromJson: Sized {
+    fn from_json(value: &JsonValue) -> Result<Self, FromJsonError>;
+
+    // What an absent object field decodes to; `None` makes the field required.
+    fn from_missing() -> Option<Self> {
+        None
+    }
+}
+
+impl FromJson for JsonValue {
+    fn from_json(value: &JsonValue) -> Result<Self, FromJsonError> {
+        Ok(value.clone())
+    }
+}
+
+impl FromJson for f64 {
+    fn from_json(value: &JsonValue) -> Result<Self, FromJsonError> {
+        value.as_f64().ok_or_else(|| FromJsonError::expected("a number", value))
+    }
+}
+
+impl FromJson for bool {
+    fn from_json(value: &JsonValue) -> Result<Self, FromJsonError> {
+        value.as_bool().ok_or_else(|| FromJsonError::expected("a boolean", value))
+    }
+}
+
+impl FromJson for String {
+    fn from_json(value: &JsonValue) -> Result<Self, FromJsonError> {
+        match value.as_str() {
+            Some(s) => Ok(s.to_string()),
+            None => Err(FromJsonError::expected("a string", value)),
+        }
+    }
+}
+
+macro_rules! impl_from_json_integer {
+    ($($t:ty),*) => {
+        $(
+            impl FromJson for $t {
+                fn from_json(value: &JsonValue) -> Result<Self, FromJsonError> {
+                    let n = f64::from_json(value)?;
+                    if n.fract() != 0.0 || n < <$t>::MIN as f64 || n >= <$t>::MAX as f64 + 1.0 {
+                        return Err(FromJsonError::new(format!(
+                            "expected an integer in {}, found {}",
+                            stringify!($t),
+                            n
+                        )));
+                    }
+                    Ok(n as $t)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_json_integer!(i32, i64, u32, u64, usize);
+
+// `null` and absent fields both decode to `None`.
+impl<T: FromJson> FromJson for Option<T> {
+    fn from_json(value: &JsonValue) -> Result<Self, FromJsonError> {
+        if value.is_null() {
+            return Ok(None);
+        }
+        T::from_json(value).map(Some)
+    }
+
+    fn from_missing() -> Option<Self> {
+        Some(None)
+    }
+}
+
+impl<T: FromJson> FromJson for Vec<T> {
+    fn from_json(value: &JsonValue) -> Result<Self, FromJsonError> {
+        let items = value.as_array().ok_or_else(|| FromJsonError::expected("an array", value))?;
+        (0..items.len()).map(|index| element(items, index)).collect()
+    }
+}
+
+// Helpers called by the derived implementations.
+
+pub fn expect_object(value: &JsonValue) -> Result<&JsonObject, FromJsonError> {
+    value.as_object().ok_or_else(|| FromJsonError::expected("an object", value))
+}
+
+pub fn field<T: FromJson>(object: &JsonObject, key: &str) -> Result<T, FromJsonError> {
+    match object.get(key) {
+        Some(value) => T::from_json(value).map_err(|err| err.at_key(key)),
+        None => T::from_missing().ok_or_else(|| FromJsonError::new("missing field").at_key(key)),
+    }
+}
+
+pub fn field_or_else<T: FromJson>(
+    object: &JsonObject,
+    key: &str,
+    default: impl FnOnce() -> T,
+) -> Result<T, FromJsonError> {
+    match object.get(key) {
+        Some(value) => T::from_json(value).map_err(|err| err.at_key(key)),
+        None => Ok(default()),
+    }
+}
+
+pub fn element<T: FromJson>(items: &[JsonValue], index: usize) -> Result<T, FromJsonError> {
+    T::from_json(&items[index]).map_err(|err| err.at_index(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::Pair;
+    use crate::lexer::parse_file_with;
+    use crate::parser::{ParseOptions, parse_tokens_with};
+
+    fn parse(input: &str) -> JsonValue {
+        let options = ParseOptions::default();
+        parse_tokens_with(&parse_file_with(input.as_bytes(), &options).unwrap(), &options).unwrap()
+    }
+
+    fn decode<T: FromJson>(input: &str) -> Result<T, String> {
+        T::from_json(&parse(input)).map_err(|err| err.to_string())
+    }
+
+    fn default_speed() -> f64 {
+        1.5
+    }
+
+    #[derive(Debug, PartialEq, FromJson)]
+    struct Fix {
+        #[json(rename = "latitude")]
+        lat: f64,
+        #[json(rename = "longitude")]
+        lng: f64,
+        #[json(default)]
+        label: String,
+        #[json(default = "default_speed")]
+        speed: f64,
+        #[json(default)]
+        tags: Vec<String>,
+        note: Option<String>,
+    }
+
+    #[derive(Debug, FromJson)]
+    struct Track {
+        fixes: Vec<Fix>,
+        #[json(rename = "legs")]
+        pairs: Vec<Pair>,
+    }
+
+    #[test]
+    fn rename_and_defaults() {
+        let fix: Fix = decode(r#"{"latitude": 1, "longitude": 2, "lat": 99}"#).unwrap();
+        assert_eq!(
+            fix,
+            Fix { lat: 1.0, lng: 2.0, label: String::new(), speed: 1.5, tags: Vec::new(), note: None }
+        );
+
+        let fix: Fix = decode(r#"{"latitude": 1, "longitude": 2, "label": "a", "speed": 3, "tags": ["x"], "note": "n"}"#).unwrap();
+        assert_eq!((fix.label.as_str(), fix.speed, &fix.tags[..], fix.note.as_deref()), ("a", 3.0, &["x".to_string()][..], Some("n")));
+
+        // A renamed field is only read from its new key.
+        assert_eq!(decode::<Fix>(r#"{"lat": 1, "longitude": 2}"#).unwrap_err(), "$.latitude: missing field");
+        // Defaults only cover absent keys, not values of the wrong type.
+        assert_eq!(decode::<Fix>(r#"{"latitude": 1, "longitude": 2, "speed": "fast"}"#).unwrap_err(), "$.speed: expected a number, found a string");
+    }
+
+    #[test]
+    fn from_array() {
+        let pair: Pair = decode("[1, 2, 3, 4]").unwrap();
+        assert_eq!((pair.x0, pair.y0, pair.x1, pair.y1), (1.0, 2.0, 3.0, 4.0));
+        let pair: Pair = decode(r#"{"y1": 4, "x1": 3, "y0": 2, "x0": 1}"#).unwrap();
+        assert_eq!((pair.x0, pair.y0, pair.x1, pair.y1), (1.0, 2.0, 3.0, 4.0));
+
+        assert_eq!(decode::<Pair>("[1, 2, 3]").unwrap_err(), "$: expected an array of 4 elements, found 3");
+        assert_eq!(decode::<Pair>(r#"[1, 2, "3", 4]"#).unwrap_err(), "$[2]: expected a number, found a string");
+        // Without `from_array` an array is rejected.
+        assert_eq!(decode::<Fix>("[1, 2]").unwrap_err(), "$: expected an object, found an array");
+    }
+
+    #[test]
+    fn errors_carry_the_json_path() {
+        let input = r#"{"fixes": [{"latitude": 1, "longitude": 2}], "legs": [[1, 2, 3, 4], {"x0": 1, "y0": true, "x1": 3, "y1": 4}]}"#;
+        assert_eq!(decode::<Track>(input).unwrap_err(), "$.legs[1].y0: expected a number, found a boolean");
+
+        let input = r#"{"fixes": [{"latitude": 1, "longitude": 2}, {"latitude": 1}], "legs": []}"#;
+        assert_eq!(decode::<Track>(input).unwrap_err(), "$.fixes[1].longitude: missing field");
+
+        let track: Track = decode(r#"{"fixes": [], "legs": [[0, 0, 1, 1]]}"#).unwrap();
+        assert_eq!((track.fixes.len(), track.pairs.len()), (0, 1));
+    }
+
+    mod elsewhere {
+        // Derives against the module named by `crate`, not the default `crate::from_json`.
+        #[derive(Debug, super::FromJson)]
+        #[json(crate = "super::super")]
+        pub struct Point {
+            pub lat: f64,
+        }
+    }
+
+    #[test]
+    fn crate_path() {
+        let point: elsewhere::Point = decode(r#"{"lat": 7}"#).unwrap();
+        assert_eq!(point.lat, 7.0);
+    }
+
+    #[derive(Debug, PartialEq, FromJson)]
+    struct Tagged<T, U = Vec<f64>>
+    where
+        U: Clone,
+    {
+        tag: String,
+        value: T,
+        #[json(default)]
+        extra: Option<U>,
+    }
+
+    #[test]
+    fn generic_structs() {
+        let tagged: Tagged<f64> = decode(r#"{"tag": "a", "value": 1}"#).unwrap();
+        assert_eq!(tagged, Tagged { tag: "a".to_string(), value: 1.0, extra: None });
+
+        let tagged: Tagged<Vec<Tagged<bool>>, String> =
+            decode(r#"{"tag": "outer", "value": [{"tag": "inner", "value": true}], "extra": "x"}"#).unwrap();
+        assert_eq!(tagged.value, [Tagged { tag: "inner".to_string(), value: true, extra: None }]);
+        assert_eq!(tagged.extra.as_deref(), Some("x"));
+
+        assert_eq!(
+            decode::<Tagged<Vec<f64>>>(r#"{"tag": "a", "value": [1, "2"]}"#).unwrap_err(),
+            "$.value[1]: expected a number, found a string"
+        );
+    }
+}
@@ -1,9 +1,18 @@
+use std::fmt;
 use std::fs::File;
 use std::io::{ BufWriter };
+use std::str::FromStr;
+use rand::SeedableRng;
+use rand::rngs::SmallRng;
 use serde_json;
 use serde::Serialize;
+use crate::anchors::{ NamedPoint, check_anchors };
+use crate::from_json::FromJson;
+use crate::geodesy::{ destination_point, Point };
 
-#[derive(Debug, Clone, Serialize)]
+// Also read from the compact `[x0, y0, x1, y1]` form.
+#[derive(Debug, Clone, Serialize, FromJson)]
+#[json(from_array)]
 pub struct Pair {
     pub x0: f64,
     pub y0: f64,
@@ -28,9 +37,177 @@ impl Pair {
 
         return Pair::new(x0, y0, x1, y1);
     }
+
+    pub fn random_with_distance(rng_gen: &mut impl rand::Rng, distribution: &DistanceDistribution, earth_radius: f64) -> Pair {
+        let x0 = rng_gen.random_range(-3.0..=3.99);
+        let y0 = rng_gen.random_range(99.0..=102.0);
+
+        let bearing = rng_gen.random_range(0.0..360.0);
+        let distance = distribution.sample(rng_gen);
+        let end = destination_point(Point::new(x0, y0), bearing, distance, earth_radius);
+
+        Pair::new(x0, y0, end.lat, end.lng)
+    }
+
+    pub fn random_between_anchors(rng_gen: &mut impl rand::Rng, sampling: &AnchorSampling, earth_radius: f64) -> Pair {
+        let anchors = &sampling.anchors;
+        let from = rng_gen.random_range(0..anchors.len());
+        let mut to = rng_gen.random_range(0..anchors.len() - 1);
+        if to >= from {
+            to += 1;
+        }
+
+        let start = jitter_point(rng_gen, anchors[from].point, sampling.jitter_km, earth_radius);
+        let end = jitter_point(rng_gen, anchors[to].point, sampling.jitter_km, earth_radius);
+
+        Pair::new(start.lat, start.lng, end.lat, end.lng)
+    }
+}
+
+// Moves `point` to a uniformly random position within `jitter_km` of where it was.
+fn jitter_point(rng_gen: &mut impl rand::Rng, point: Point, jitter_km: f64, earth_radius: f64) -> Point {
+    if jitter_km <= 0.0 {
+        return point;
+    }
+    let bearing = rng_gen.random_range(0.0..360.0);
+    let distance = jitter_km * rng_gen.random_range(0.0..=1.0f64).sqrt();
+    destination_point(point, bearing, distance, earth_radius)
+}
+
+#[derive(Debug, Clone)]
+pub struct AnchorSampling {
+    pub source: String,
+    pub anchors: Vec<NamedPoint>,
+    pub jitter_km: f64,
+}
+
+#[derive(Debug, Clone)]
+pub enum PairMode {
+    Uniform,
+    Distance(DistanceDistribution),
+    Trajectory(TrajectoryConfig),
+    Anchors(AnchorSampling),
+}
+
+impl PairMode {
+    // One-line description stored in the metrics file so the run can be regenerated from its seed.
+    pub fn describe(&self) -> String {
+        match self {
+            PairMode::Uniform => "uniform".to_string(),
+            PairMode::Distance(distribution) => format!("distance {}", distribution),
+            PairMode::Trajectory(config) => format!(
+                "trajectory {} {} {} {}",
+                config.tracks, config.step_km, config.speed_noise, config.heading_noise
+            ),
+            PairMode::Anchors(sampling) => format!("anchors {} {}", sampling.jitter_km, sampling.source),
+        }
+    }
+
+    // Rebuilds the mode from `describe` and, for anchors mode, the anchors recorded next to it;
+    // the anchor file named in the description is only a label and is not read again.
+    pub fn from_description(description: &str, anchors: &[NamedPoint]) -> Result<PairMode, Box<dyn std::error::Error>> {
+        let description = description.trim();
+        let (kind, args) = description.split_once(' ').unwrap_or((description, ""));
+
+        match kind {
+            "" | "uniform" => Ok(PairMode::Uniform),
+            "distance" => Ok(PairMode::Distance(args.parse()?)),
+            "trajectory" => {
+                let fields: Vec<&str> = args.split_whitespace().collect();
+                let [tracks, step_km, speed_noise, heading_noise] = fields[..] else {
+                    return Err(format!("Malformed trajectory mode '{}'", args).into());
+                };
+                Ok(PairMode::Trajectory(TrajectoryConfig {
+                    tracks: tracks.parse()?,
+                    step_km: step_km.parse()?,
+                    speed_noise: speed_noise.parse()?,
+                    heading_noise: heading_noise.parse()?,
+                }))
+            }
+            "anchors" => {
+                let (jitter_km, source) = args
+                    .split_once(' ')
+                    .ok_or_else(|| format!("Malformed anchors mode '{}'", args))?;
+                check_anchors(source, anchors)?;
+                Ok(PairMode::Anchors(AnchorSampling {
+                    source: source.to_string(),
+                    anchors: anchors.to_vec(),
+                    jitter_km: jitter_km.parse()?,
+                }))
+            }
+            _ => Err(format!("Unknown generation mode '{}'", kind).into()),
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize)]
+// Deterministic stream of generated pairs for a mode and seed, tagged with the segment (track)
+// each pair belongs to. Runs that are not trajectories consist of a single segment.
+pub struct PairStream<'a> {
+    mode: &'a PairMode,
+    rng_gen: SmallRng,
+    earth_radius: f64,
+    segments: Vec<u64>,
+    segment: usize,
+    next_segment: usize,
+    remaining: u64,
+    walker: Option<TrackWalker>,
+}
+
+impl<'a> PairStream<'a> {
+    pub fn new(mode: &'a PairMode, seed: u64, num_pairs: u64, earth_radius: f64) -> Self {
+        let segments = match mode {
+            PairMode::Trajectory(config) => config.pairs_per_track(num_pairs),
+            _ => vec![num_pairs],
+        };
+
+        PairStream {
+            mode,
+            rng_gen: SmallRng::seed_from_u64(seed),
+            earth_radius,
+            segments,
+            segment: 0,
+            next_segment: 0,
+            remaining: 0,
+            walker: None,
+        }
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+}
+
+impl Iterator for PairStream<'_> {
+    type Item = (usize, Pair);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.remaining == 0 {
+            self.remaining = *self.segments.get(self.next_segment)?;
+            self.segment = self.next_segment;
+            self.next_segment += 1;
+            self.walker = match self.mode {
+                PairMode::Trajectory(config) => Some(TrackWalker::new(&mut self.rng_gen, *config)),
+                _ => None,
+            };
+        }
+        self.remaining -= 1;
+
+        let rng_gen = &mut self.rng_gen;
+        let pair = match (&mut self.walker, self.mode) {
+            (Some(walker), _) => walker.next_pair(rng_gen, self.earth_radius),
+            (None, PairMode::Distance(distribution)) => {
+                Pair::random_with_distance(rng_gen, distribution, self.earth_radius)
+            }
+            (None, PairMode::Anchors(sampling)) => {
+                Pair::random_between_anchors(rng_gen, sampling, self.earth_radius)
+            }
+            (None, _) => Pair::random_new(rng_gen),
+        };
+        Some((self.segment, pair))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, FromJson)]
 pub struct Pairs {
     pairs: Vec<Pair>,
 }
@@ -46,10 +223,399 @@ impl Pairs {
         self.pairs.push(pair);
     }
 
+    pub fn as_slice(&self) -> &[Pair] {
+        &self.pairs
+    }
+
     pub fn save_to_file(&self, filename: &str) -> Result<(), Box<dyn std::error::Error>>{
        let file = File::create(filename)?;
        let writer = BufWriter::new(file);
        serde_json::to_writer_pretty(writer, &self)?;
        Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TrajectoryConfig {
+    pub tracks: u64,
+    pub step_km: f64,
+    pub speed_noise: f64,
+    pub heading_noise: f64,
+}
+
+impl TrajectoryConfig {
+    pub fn validate(&self, num_pairs: u64) -> Result<(), String> {
+        if self.tracks == 0 || self.tracks > num_pairs {
+            return Err(format!("track count must be between 1 and {}", num_pairs));
+        }
+        if !self.step_km.is_finite() || self.step_km <= 0.0 {
+            return Err(format!("invalid step length {} km", self.step_km));
+        }
+        if !(0.0..=1.0).contains(&self.speed_noise) {
+            return Err(format!("speed noise must be within 0..=1, got {}", self.speed_noise));
+        }
+        if !(0.0..=180.0).contains(&self.heading_noise) {
+            return Err(format!("heading noise must be within 0..=180 degrees, got {}", self.heading_noise));
+        }
+        Ok(())
+    }
+
+    // Splits `num_pairs` into per-track pair counts, spreading the remainder over the first tracks.
+    pub fn pairs_per_track(&self, num_pairs: u64) -> Vec<u64> {
+        let base = num_pairs / self.tracks;
+        let remainder = num_pairs % self.tracks;
+        (0..self.tracks)
+            .map(|track| if track < remainder { base + 1 } else { base })
+            .collect()
+    }
+}
+
+pub struct TrackWalker {
+    config: TrajectoryConfig,
+    position: Point,
+    heading: f64,
+}
+
+impl TrackWalker {
+    pub fn new(rng_gen: &mut impl rand::Rng, config: TrajectoryConfig) -> Self {
+        let lat = rng_gen.random_range(-3.0..=3.99);
+        let lng = rng_gen.random_range(99.0..=102.0);
+        let heading = rng_gen.random_range(0.0..360.0);
+
+        TrackWalker {
+            config,
+            position: Point::new(lat, lng),
+            heading,
+        }
+    }
+
+    // Advances the walk by one fix and returns the segment from the previous fix to the new one.
+    pub fn next_pair(&mut self, rng_gen: &mut impl rand::Rng, earth_radius: f64) -> Pair {
+        let noise = self.config.speed_noise;
+        let step = self.config.step_km * (1.0 + rng_gen.random_range(-noise..=noise));
+        let turn = self.config.heading_noise;
+        self.heading = (self.heading + rng_gen.random_range(-turn..=turn) + 360.0) % 360.0;
+
+        let next = destination_point(self.position, self.heading, step, earth_radius);
+        let pair = Pair::new(self.position.lat, self.position.lng, next.lat, next.lng);
+        self.position = next;
+
+        pair
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HistogramBin {
+    pub min: f64,
+    pub max: f64,
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone)]
+pub enum DistanceDistribution {
+    Uniform { min: f64, max: f64 },
+    LogUniform { min: f64, max: f64 },
+    Histogram(Vec<HistogramBin>),
+}
+
+impl DistanceDistribution {
+    pub fn sample(&self, rng_gen: &mut impl rand::Rng) -> f64 {
+        match self {
+            DistanceDistribution::Uniform { min, max } => rng_gen.random_range(*min..=*max),
+            DistanceDistribution::LogUniform { min, max } => {
+                rng_gen.random_range(min.ln()..=max.ln()).exp()
+            }
+            DistanceDistribution::Histogram(bins) => {
+                let total: f64 = bins.iter().map(|bin| bin.weight).sum();
+                let mut target = rng_gen.random_range(0.0..total);
+                for bin in bins {
+                    if target < bin.weight {
+                        return rng_gen.random_range(bin.min..=bin.max);
+                    }
+                    target -= bin.weight;
+                }
+                let last = bins.last().unwrap();
+                rng_gen.random_range(last.min..=last.max)
+            }
+        }
+    }
+
+    fn max_distance(&self) -> f64 {
+        match self {
+            DistanceDistribution::Uniform { max, .. } | DistanceDistribution::LogUniform { max, .. } => *max,
+            DistanceDistribution::Histogram(bins) => bins.iter().map(|bin| bin.max).fold(0.0, f64::max),
+        }
+    }
+
+    pub fn validate(&self, earth_radius: f64) -> Result<(), String> {
+        let half_circumference = std::f64::consts::PI * earth_radius;
+        if self.max_distance() > half_circumference {
+            return Err(format!(
+                "distances above {:.1} km are not reachable on a sphere of radius {} km",
+                half_circumference, earth_radius
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn parse_range(spec: &str, separator: char) -> Result<(f64, f64), String> {
+    let (min, max) = spec
+        .split_once(separator)
+        .ok_or_else(|| format!("expected MIN{}MAX, got '{}'", separator, spec))?;
+    let min: f64 = min.trim().parse().map_err(|_| format!("invalid distance '{}'", min))?;
+    let max: f64 = max.trim().parse().map_err(|_| format!("invalid distance '{}'", max))?;
+    if !min.is_finite() || !max.is_finite() || min < 0.0 || min > max {
+        return Err(format!("invalid distance range {}..{}", min, max));
+    }
+    Ok((min, max))
+}
+
+impl fmt::Display for DistanceDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DistanceDistribution::Uniform { min, max } => write!(f, "uniform:{},{}", min, max),
+            DistanceDistribution::LogUniform { min, max } => write!(f, "log-uniform:{},{}", min, max),
+            DistanceDistribution::Histogram(bins) => {
+                write!(f, "histogram:")?;
+                for (i, bin) in bins.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}-{}={}", bin.min, bin.max, bin.weight)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl FromStr for DistanceDistribution {
+    type Err = String;
+
+    // Accepts `uniform:MIN,MAX`, `log-uniform:MIN,MAX` or `histogram:MIN-MAX=WEIGHT,...` (km).
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let (kind, args) = spec
+            .split_once(':')
+            .ok_or_else(|| format!("expected KIND:ARGS, got '{}'", spec))?;
+
+        match kind {
+            "uniform" => {
+                let (min, max) = parse_range(args, ',')?;
+                Ok(DistanceDistribution::Uniform { min, max })
+            }
+            "log-uniform" => {
+                let (min, max) = parse_range(args, ',')?;
+                if min <= 0.0 {
+                    return Err("log-uniform distances must be greater than zero".to_string());
+                }
+                Ok(DistanceDistribution::LogUniform { min, max })
+            }
+            "histogram" => {
+                let mut bins = Vec::new();
+                for bin in args.split(',') {
+                    let (range, weight) = bin
+                        .split_once('=')
+                        .ok_or_else(|| format!("expected MIN-MAX=WEIGHT, got '{}'", bin))?;
+                    let (min, max) = parse_range(range, '-')?;
+                    let weight: f64 = weight.trim().parse().map_err(|_| format!("invalid weight '{}'", weight))?;
+                    if !weight.is_finite() || weight < 0.0 {
+                        return Err(format!("invalid weight {}", weight));
+                    }
+                    bins.push(HistogramBin { min, max, weight });
+                }
+                if bins.iter().map(|bin| bin.weight).sum::<f64>() <= 0.0 {
+                    return Err("histogram weights must not all be zero".to_string());
+                }
+                Ok(DistanceDistribution::Histogram(bins))
+            }
+            _ => Err(format!("unknown distance distribution '{}'", kind)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geodesy::initial_bearing;
+    use crate::haversine::reference_haversine;
+
+    const EARTH_RADIUS: f64 = 6372.8;
+
+    fn coordinates(pair: &Pair) -> [u64; 4] {
+        [pair.x0, pair.y0, pair.x1, pair.y1].map(f64::to_bits)
+    }
+
+    #[test]
+    fn pair_streams_repeat_for_a_seed() {
+        let modes = [
+            PairMode::Uniform,
+            PairMode::Distance("histogram:0-10=1,100-200=3".parse().unwrap()),
+            PairMode::Trajectory(TrajectoryConfig { tracks: 3, step_km: 1.0, speed_noise: 0.5, heading_noise: 30.0 }),
+        ];
+        for mode in &modes {
+            let run = |seed| {
+                PairStream::new(mode, seed, 100, EARTH_RADIUS)
+                    .map(|(segment, pair)| (segment, coordinates(&pair)))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(run(7).len(), 100);
+            assert_eq!(run(7), run(7), "{}", mode.describe());
+            assert_ne!(run(7), run(8), "{}", mode.describe());
+        }
+    }
+
+    #[test]
+    fn trajectory_streams_walk_each_track_in_turn() {
+        let config = TrajectoryConfig { tracks: 4, step_km: 2.0, speed_noise: 0.1, heading_noise: 5.0 };
+        let mode = PairMode::Trajectory(config);
+        let stream = PairStream::new(&mode, 3, 10, EARTH_RADIUS);
+        assert_eq!(stream.segment_count(), 4);
+
+        let pairs: Vec<(usize, Pair)> = stream.collect();
+        let segments: Vec<usize> = pairs.iter().map(|(segment, _)| *segment).collect();
+        assert_eq!(segments, [0, 0, 0, 1, 1, 1, 2, 2, 3, 3]);
+        for window in pairs.windows(2) {
+            let [(segment, previous), (next_segment, next)] = window else { unreachable!() };
+            // A track continues from its last fix; a new track starts somewhere else.
+            let continues = previous.x1 == next.x0 && previous.y1 == next.y0;
+            assert_eq!(continues, segment == next_segment);
+        }
+    }
+
+    // Distances of pairs drawn from `distribution`, measured back with `reference_haversine`.
+    fn sampled_distances(distribution: &DistanceDistribution, count: usize) -> Vec<f64> {
+        let mut rng_gen = SmallRng::seed_from_u64(27);
+        (0..count)
+            .map(|_| {
+                let pair = Pair::random_with_distance(&mut rng_gen, distribution, EARTH_RADIUS);
+                reference_haversine(&pair, EARTH_RADIUS)
+            })
+            .collect()
+    }
+
+    fn within(distance: f64, min: f64, max: f64) -> bool {
+        let tolerance = 1e-9 * max.max(1.0);
+        distance >= min - tolerance && distance <= max + tolerance
+    }
+
+    #[test]
+    fn distances_fall_within_the_requested_range() {
+        for spec in ["uniform:0,0.5", "uniform:100,150", "uniform:19000,20000", "log-uniform:0.01,5000", "uniform:42,42"] {
+            let distribution: DistanceDistribution = spec.parse().unwrap();
+            let (DistanceDistribution::Uniform { min, max } | DistanceDistribution::LogUniform { min, max }) = distribution else {
+                unreachable!();
+            };
+            let distances = sampled_distances(&distribution, 2000);
+            for &distance in &distances {
+                assert!(within(distance, min, max), "{spec}: {distance}");
+            }
+            // The samples spread over the range rather than clustering at one end.
+            if min < max {
+                let middle = match distribution {
+                    DistanceDistribution::LogUniform { .. } => (min * max).sqrt(),
+                    _ => (min + max) / 2.0,
+                };
+                let below = distances.iter().filter(|&&distance| distance < middle).count();
+                assert!((800..1200).contains(&below), "{spec}: {below} of 2000 below {middle}");
+            }
+        }
+    }
+
+    #[test]
+    fn histogram_distances_follow_the_bin_weights() {
+        let distribution: DistanceDistribution = "histogram:1-2=1,10-20=3,50-60=0".parse().unwrap();
+        let distances = sampled_distances(&distribution, 4000);
+        let in_bin = |min, max| distances.iter().filter(|&&distance| within(distance, min, max)).count();
+        let (first, second, empty) = (in_bin(1.0, 2.0), in_bin(10.0, 20.0), in_bin(50.0, 60.0));
+        assert_eq!(first + second, distances.len());
+        assert_eq!(empty, 0);
+        assert!((800..1200).contains(&first), "{first} of 4000 in the weight-1 bin");
+    }
+
+    #[test]
+    fn distributions_round_trip_through_their_text_form() {
+        for spec in ["uniform:0,10", "uniform:1.5,2.25", "log-uniform:0.001,20000", "histogram:0-1=1", "histogram:0-1=0.5,1-10=2,10-100=0"] {
+            let distribution: DistanceDistribution = spec.parse().unwrap();
+            assert_eq!(distribution.to_string(), spec);
+            assert_eq!(distribution.to_string().parse::<DistanceDistribution>().unwrap().to_string(), spec);
+        }
+        assert_eq!("uniform: 3 , 4 ".parse::<DistanceDistribution>().unwrap().to_string(), "uniform:3,4");
+    }
+
+    #[test]
+    fn malformed_distributions_are_rejected() {
+        for (spec, message) in [
+            ("uniform", "expected KIND:ARGS, got 'uniform'"),
+            ("normal:1,2", "unknown distance distribution 'normal'"),
+            ("uniform:1", "expected MIN,MAX, got '1'"),
+            ("uniform:a,2", "invalid distance 'a'"),
+            ("uniform:5,1", "invalid distance range 5..1"),
+            ("uniform:-1,1", "invalid distance range -1..1"),
+            ("uniform:0,inf", "invalid distance range 0..inf"),
+            ("log-uniform:0,10", "log-uniform distances must be greater than zero"),
+            ("histogram:1-2", "expected MIN-MAX=WEIGHT, got '1-2'"),
+            ("histogram:1-2=x", "invalid weight 'x'"),
+            ("histogram:1-2=-1", "invalid weight -1"),
+            ("histogram:1-2=0,3-4=0", "histogram weights must not all be zero"),
+        ] {
+            assert_eq!(spec.parse::<DistanceDistribution>().unwrap_err(), message, "{spec}");
+        }
+
+        let half_circumference: DistanceDistribution = "uniform:0,20020".parse().unwrap();
+        assert!(half_circumference.validate(EARTH_RADIUS).is_ok());
+        let too_far: DistanceDistribution = "histogram:0-1=1,20000-20100=1".parse().unwrap();
+        assert_eq!(
+            too_far.validate(EARTH_RADIUS).unwrap_err(),
+            "distances above 20020.7 km are not reachable on a sphere of radius 6372.8 km"
+        );
+    }
+
+    fn track(tracks: u64) -> TrajectoryConfig {
+        TrajectoryConfig { tracks, step_km: 0.05, speed_noise: 0.2, heading_noise: 10.0 }
+    }
+
+    #[test]
+    fn trajectory_configs_are_validated() {
+        assert!(track(1).validate(1).is_ok());
+        assert!(track(10).validate(10).is_ok());
+        assert_eq!(track(11).validate(10).unwrap_err(), "track count must be between 1 and 10");
+        assert_eq!(track(0).validate(10).unwrap_err(), "track count must be between 1 and 10");
+        assert_eq!(track(1).validate(0).unwrap_err(), "track count must be between 1 and 0");
+        for (config, message) in [
+            (TrajectoryConfig { step_km: 0.0, ..track(1) }, "invalid step length 0 km"),
+            (TrajectoryConfig { step_km: f64::NAN, ..track(1) }, "invalid step length NaN km"),
+            (TrajectoryConfig { speed_noise: 1.5, ..track(1) }, "speed noise must be within 0..=1, got 1.5"),
+            (TrajectoryConfig { heading_noise: -1.0, ..track(1) }, "heading noise must be within 0..=180 degrees, got -1"),
+        ] {
+            assert_eq!(config.validate(10).unwrap_err(), message);
+        }
+    }
+
+    #[test]
+    fn pairs_are_spread_over_tracks() {
+        assert_eq!(track(3).pairs_per_track(10), [4, 3, 3]);
+        assert_eq!(track(5).pairs_per_track(5), [1; 5]);
+        assert_eq!(track(4).pairs_per_track(1_000_003).iter().sum::<u64>(), 1_000_003);
+    }
+
+    #[test]
+    fn track_walkers_step_and_turn_within_the_noise() {
+        let config = TrajectoryConfig { tracks: 1, step_km: 3.0, speed_noise: 0.25, heading_noise: 20.0 };
+        let mut rng_gen = SmallRng::seed_from_u64(28);
+        let mut walker = TrackWalker::new(&mut rng_gen, config);
+
+        let steps: Vec<Pair> = (0..500).map(|_| walker.next_pair(&mut rng_gen, EARTH_RADIUS)).collect();
+        for step in &steps {
+            let distance = reference_haversine(step, EARTH_RADIUS);
+            assert!((2.25 - 1e-9..=3.75 + 1e-9).contains(&distance), "step of {distance} km");
+        }
+        for window in steps.windows(2) {
+            let [previous, next] = window else { unreachable!() };
+            assert_eq!((previous.x1, previous.y1), (next.x0, next.y0));
+            // Each step leaves on the previous step's heading plus a turn of at most 20 degrees.
+            let turn = (initial_bearing(next) - initial_bearing(previous) + 540.0) % 360.0 - 180.0;
+            assert!(turn.abs() <= 20.0 + 1e-6, "turn of {turn} degrees");
+        }
+    }
+}
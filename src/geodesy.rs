@@ -0,0 +1,267 @@
+// Great-circle operations on `Pair` data. The generator only needs `destination_point`; the rest
+// are exercised by the tests below.
+
+use crate::generator::Pair;
+use crate::haversine::{radians_from_degrees, reference_haversine};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub lat: f64,
+    pub lng: f64,
+}
+
+impl Point {
+    pub fn new(lat: f64, lng: f64) -> Self {
+        Point { lat, lng }
+    }
+}
+
+#[inline]
+pub fn degrees_from_radians(radians: f64) -> f64 {
+    radians.to_degrees()
+}
+
+#[inline]
+fn normalise_bearing(degrees: f64) -> f64 {
+    (degrees + 360.0) % 360.0
+}
+
+#[inline]
+fn normalise_longitude(degrees: f64) -> f64 {
+    (degrees + 540.0) % 360.0 - 180.0
+}
+
+fn bearing_between(lat0: f64, lng0: f64, lat1: f64, lng1: f64) -> f64 {
+    let phi0 = radians_from_degrees(lat0);
+    let phi1 = radians_from_degrees(lat1);
+    let d_lon = radians_from_degrees(lng1 - lng0);
+
+    let y = d_lon.sin() * phi1.cos();
+    let x = phi0.cos() * phi1.sin() - phi0.sin() * phi1.cos() * d_lon.cos();
+
+    normalise_bearing(degrees_from_radians(y.atan2(x)))
+}
+
+// Bearing in degrees clockwise from north when setting off from (x0, y0) towards (x1, y1).
+#[allow(dead_code, reason = "API for track consumers; the generator keeps its own heading")]
+pub fn initial_bearing(pair: &Pair) -> f64 {
+    bearing_between(pair.x0, pair.y0, pair.x1, pair.y1)
+}
+
+// Bearing in degrees on arrival at (x1, y1); differs from the initial bearing on a great circle.
+#[allow(dead_code, reason = "API for track consumers, like initial_bearing")]
+pub fn final_bearing(pair: &Pair) -> f64 {
+    normalise_bearing(bearing_between(pair.x1, pair.y1, pair.x0, pair.y0) + 180.0)
+}
+
+#[allow(dead_code, reason = "API only; nothing generated needs a midpoint")]
+pub fn midpoint(pair: &Pair) -> Point {
+    let phi0 = radians_from_degrees(pair.x0);
+    let phi1 = radians_from_degrees(pair.x1);
+    let lambda0 = radians_from_degrees(pair.y0);
+    let d_lon = radians_from_degrees(pair.y1 - pair.y0);
+
+    let bx = phi1.cos() * d_lon.cos();
+    let by = phi1.cos() * d_lon.sin();
+
+    let phi_m = (phi0.sin() + phi1.sin()).atan2(((phi0.cos() + bx).powi(2) + by.powi(2)).sqrt());
+    let lambda_m = lambda0 + by.atan2(phi0.cos() + bx);
+
+    Point::new(
+        degrees_from_radians(phi_m),
+        normalise_longitude(degrees_from_radians(lambda_m)),
+    )
+}
+
+pub fn destination_point(start: Point, bearing: f64, distance: f64, earth_radius: f64) -> Point {
+    let delta = distance / earth_radius;
+    let theta = radians_from_degrees(bearing);
+    let phi0 = radians_from_degrees(start.lat);
+    let lambda0 = radians_from_degrees(start.lng);
+
+    let sin_phi1 = phi0.sin() * delta.cos() + phi0.cos() * delta.sin() * theta.cos();
+    let phi1 = sin_phi1.clamp(-1.0, 1.0).asin();
+    let y = theta.sin() * delta.sin() * phi0.cos();
+    let x = delta.cos() - phi0.sin() * sin_phi1;
+    let lambda1 = lambda0 + y.atan2(x);
+
+    Point::new(
+        degrees_from_radians(phi1),
+        normalise_longitude(degrees_from_radians(lambda1)),
+    )
+}
+
+// Point at `fraction` of the way along the great circle from (x0, y0) to (x1, y1).
+#[allow(dead_code, reason = "API only; trajectories step with destination_point instead")]
+pub fn intermediate_point(pair: &Pair, fraction: f64) -> Point {
+    let delta = reference_haversine(pair, 1.0);
+    if delta == 0.0 {
+        return Point::new(pair.x0, pair.y0);
+    }
+
+    let phi0 = radians_from_degrees(pair.x0);
+    let lambda0 = radians_from_degrees(pair.y0);
+    let phi1 = radians_from_degrees(pair.x1);
+    let lambda1 = radians_from_degrees(pair.y1);
+
+    let a = ((1.0 - fraction) * delta).sin() / delta.sin();
+    let b = (fraction * delta).sin() / delta.sin();
+
+    let x = a * phi0.cos() * lambda0.cos() + b * phi1.cos() * lambda1.cos();
+    let y = a * phi0.cos() * lambda0.sin() + b * phi1.cos() * lambda1.sin();
+    let z = a * phi0.sin() + b * phi1.sin();
+
+    let phi = z.atan2((x * x + y * y).sqrt());
+    let lambda = y.atan2(x);
+
+    Point::new(
+        degrees_from_radians(phi),
+        normalise_longitude(degrees_from_radians(lambda)),
+    )
+}
+
+// Signed distance from `point` to the great circle through the path; negative is left of the path.
+#[allow(dead_code, reason = "API only; no generated data is matched against a path yet")]
+pub fn cross_track_distance(path: &Pair, point: Point, earth_radius: f64) -> f64 {
+    let to_point = Pair::new(path.x0, path.y0, point.lat, point.lng);
+    let delta13 = reference_haversine(&to_point, 1.0);
+    let theta13 = radians_from_degrees(initial_bearing(&to_point));
+    let theta12 = radians_from_degrees(initial_bearing(path));
+
+    let delta_xt = (delta13.sin() * (theta13 - theta12).sin()).clamp(-1.0, 1.0).asin();
+
+    delta_xt * earth_radius
+}
+
+// Distance from the path start to the point on the path closest to `point`; negative if behind the start.
+#[allow(dead_code, reason = "API only; the counterpart of cross_track_distance")]
+pub fn along_track_distance(path: &Pair, point: Point, earth_radius: f64) -> f64 {
+    let to_point = Pair::new(path.x0, path.y0, point.lat, point.lng);
+    let delta13 = reference_haversine(&to_point, 1.0);
+    let theta13 = radians_from_degrees(initial_bearing(&to_point));
+    let theta12 = radians_from_degrees(initial_bearing(path));
+
+    let delta_xt = (delta13.sin() * (theta13 - theta12).sin()).clamp(-1.0, 1.0).asin();
+    let delta_at = (delta13.cos() / delta_xt.cos()).clamp(-1.0, 1.0).acos();
+
+    delta_at * (theta12 - theta13).cos().signum() * earth_radius
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    const EARTH_RADIUS: f64 = 6372.8;
+    // Kilometres; the helpers lose a few ulps of an angle through the trigonometry.
+    const TOLERANCE: f64 = 1e-6;
+
+    fn random_point(rng: &mut SmallRng) -> Point {
+        Point::new(rng.random_range(-80.0..=80.0), rng.random_range(-180.0..180.0))
+    }
+
+    fn distance(from: Point, to: Point) -> f64 {
+        reference_haversine(&Pair::new(from.lat, from.lng, to.lat, to.lng), EARTH_RADIUS)
+    }
+
+    // Random pairs that are neither coincident nor close to antipodal, where bearings are undefined.
+    fn random_pairs(seed: u64) -> impl Iterator<Item = Pair> {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        std::iter::repeat_with(move || {
+            let from = random_point(&mut rng);
+            let to = random_point(&mut rng);
+            Pair::new(from.lat, from.lng, to.lat, to.lng)
+        })
+        .filter(|pair| {
+            let d = reference_haversine(pair, EARTH_RADIUS);
+            d > 1.0 && d < std::f64::consts::PI * EARTH_RADIUS - 100.0
+        })
+        .take(1000)
+    }
+
+    #[test]
+    fn destination_point_round_trips_the_distance() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        for _ in 0..1000 {
+            let start = random_point(&mut rng);
+            let bearing = rng.random_range(0.0..360.0);
+            let travelled = rng.random_range(0.0..10_000.0);
+            let end = destination_point(start, bearing, travelled, EARTH_RADIUS);
+            assert!((distance(start, end) - travelled).abs() < TOLERANCE, "{start:?} {bearing} {travelled}");
+        }
+    }
+
+    #[test]
+    fn destination_point_along_initial_bearing_reaches_the_end() {
+        for pair in random_pairs(2) {
+            let start = Point::new(pair.x0, pair.y0);
+            let end = destination_point(start, initial_bearing(&pair), reference_haversine(&pair, EARTH_RADIUS), EARTH_RADIUS);
+            assert!(distance(end, Point::new(pair.x1, pair.y1)) < TOLERANCE, "{pair:?}");
+        }
+    }
+
+    #[test]
+    fn final_bearing_is_the_reversed_initial_bearing() {
+        for pair in random_pairs(3) {
+            let reversed = Pair::new(pair.x1, pair.y1, pair.x0, pair.y0);
+            let expected = normalise_bearing(initial_bearing(&reversed) + 180.0);
+            let difference = (final_bearing(&pair) - expected).abs();
+            assert!(difference < 1e-9 || (360.0 - difference) < 1e-9, "{pair:?}");
+            assert!((0.0..360.0).contains(&final_bearing(&pair)));
+        }
+    }
+
+    #[test]
+    fn midpoint_is_equidistant_and_halfway() {
+        for pair in random_pairs(4) {
+            let total = reference_haversine(&pair, EARTH_RADIUS);
+            let mid = midpoint(&pair);
+            let to_start = distance(Point::new(pair.x0, pair.y0), mid);
+            let to_end = distance(mid, Point::new(pair.x1, pair.y1));
+            assert!((to_start - to_end).abs() < TOLERANCE, "{pair:?}");
+            assert!((to_start - total / 2.0).abs() < TOLERANCE, "{pair:?}");
+        }
+    }
+
+    #[test]
+    fn intermediate_point_splits_the_distance_by_fraction() {
+        let mut rng = SmallRng::seed_from_u64(5);
+        for pair in random_pairs(5) {
+            let total = reference_haversine(&pair, EARTH_RADIUS);
+            let fraction = rng.random_range(0.0..=1.0);
+            let point = intermediate_point(&pair, fraction);
+            assert!((distance(Point::new(pair.x0, pair.y0), point) - fraction * total).abs() < TOLERANCE, "{pair:?}");
+            assert!((distance(point, Point::new(pair.x1, pair.y1)) - (1.0 - fraction) * total).abs() < TOLERANCE, "{pair:?}");
+        }
+        let mid = midpoint(&Pair::new(10.0, 20.0, 30.0, 40.0));
+        let halfway = intermediate_point(&Pair::new(10.0, 20.0, 30.0, 40.0), 0.5);
+        assert!(distance(mid, halfway) < TOLERANCE);
+    }
+
+    #[test]
+    fn cross_track_is_zero_on_the_path() {
+        let mut rng = SmallRng::seed_from_u64(6);
+        for pair in random_pairs(6) {
+            let fraction = rng.random_range(0.0..=1.0);
+            let point = intermediate_point(&pair, fraction);
+            let total = reference_haversine(&pair, EARTH_RADIUS);
+            assert!(cross_track_distance(&pair, point, EARTH_RADIUS).abs() < TOLERANCE, "{pair:?}");
+            let along = along_track_distance(&pair, point, EARTH_RADIUS);
+            assert!((along - fraction * total).abs() < 1e-3, "{pair:?} {along} {}", fraction * total);
+        }
+    }
+
+    #[test]
+    fn cross_track_matches_distance_to_offset_point() {
+        let mut rng = SmallRng::seed_from_u64(7);
+        for pair in random_pairs(7) {
+            // Step off the path at right angles from its start; the offset is then the cross-track distance.
+            let offset = rng.random_range(1.0..500.0);
+            let right = normalise_bearing(initial_bearing(&pair) + 90.0);
+            let point = destination_point(Point::new(pair.x0, pair.y0), right, offset, EARTH_RADIUS);
+            assert!((cross_track_distance(&pair, point, EARTH_RADIUS) - offset).abs() < 1e-6, "{pair:?}");
+            assert!(along_track_distance(&pair, point, EARTH_RADIUS).abs() < 1e-3, "{pair:?}");
+        }
+    }
+}
@@ -1,11 +1,13 @@
 use std::fs::File;
 use std::io::{ Write, BufWriter, BufReader, Read, BufRead };
+use crate::anchors::NamedPoint;
 use crate::generator::{ Pair };
+use crate::geodesy::Point;
 
 const DEGREES_TO_RADIANS: f64 = 0.01745329251994329577;
 
 #[inline]
-fn radians_from_degrees(degrees: f64) -> f64 {
+pub fn radians_from_degrees(degrees: f64) -> f64 {
     DEGREES_TO_RADIANS * degrees
 }
 
@@ -28,12 +30,36 @@ pub fn reference_haversine(pair: &Pair, earth_radius: f64) -> f64 {
 }
 
 
-pub fn save_run_metrics(distances: &Vec<f64>, seed: u64, num_pairs: u64, cumu_distance: f64, metrics_output: &str, distance_output: &str) -> Result<(), Box<dyn std::error::Error>> {
+#[derive(Debug, Clone, Default)]
+pub struct TrackMetrics {
+    pub pairs: u64,
+    pub distance: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RunMetrics {
+    pub seed: u64,
+    pub points: u64,
+    pub est_distance: f64,
+    pub mode: String,
+    pub tracks: Vec<TrackMetrics>,
+    // The anchors an anchors-mode run sampled from, so verify does not depend on the anchor file.
+    pub anchors: Vec<NamedPoint>,
+}
+
+pub fn save_run_metrics(distances: &Vec<f64>, metrics: &RunMetrics, metrics_output: &str, distance_output: &str) -> Result<(), Box<dyn std::error::Error>> {
     { 
         let mut file = File::create(metrics_output)?;
-        writeln!(file, "Seed: {}", seed)?;
-        writeln!(file, "Points: {}", num_pairs)?;
-        writeln!(file, "Est Distance: {}", cumu_distance)?;
+        writeln!(file, "Seed: {}", metrics.seed)?;
+        writeln!(file, "Points: {}", metrics.points)?;
+        writeln!(file, "Est Distance: {}", metrics.est_distance)?;
+        writeln!(file, "Mode: {}", metrics.mode)?;
+        for track in &metrics.tracks {
+            writeln!(file, "Track: {} {}", track.pairs, track.distance)?;
+        }
+        for anchor in &metrics.anchors {
+            writeln!(file, "Anchor: {} {} {}", anchor.point.lat, anchor.point.lng, anchor.name)?;
+        }
     }
 
     {
@@ -44,9 +70,12 @@ pub fn save_run_metrics(distances: &Vec<f64>, seed: u64, num_pairs: u64, cumu_di
         }
     }
 
-    println!("Seed: {}", seed);
-    println!("Points: {}", num_pairs);
-    println!("Est Distance: {}", cumu_distance);
+    println!("Seed: {}", metrics.seed);
+    println!("Points: {}", metrics.points);
+    println!("Est Distance: {}", metrics.est_distance);
+    if !metrics.tracks.is_empty() {
+        println!("Tracks: {}", metrics.tracks.len());
+    }
 
     Ok(())
 }
@@ -65,24 +94,39 @@ pub fn read_distances_from_file(filename: &str) -> Result<Vec<f64>, Box<dyn std:
     Ok(distances)
 }
 
-pub fn read_run_metrics(filename: &str) -> Result<(u64, u64, f64), Box<dyn std::error::Error>> {
+pub fn read_run_metrics(filename: &str) -> Result<RunMetrics, Box<dyn std::error::Error>> {
     let file = File::open(filename)?;
     let reader = BufReader::new(file);
     
-    let mut seed = 0;
-    let mut points = 0;
-    let mut est_distance = 0.0;
+    let mut metrics = RunMetrics::default();
     
     for line in reader.lines() {
         let line = line?;
         if line.starts_with("Seed: ") {
-            seed = line[6..].parse()?;
+            metrics.seed = line[6..].parse()?;
         } else if line.starts_with("Points: ") {
-            points = line[8..].parse()?;
+            metrics.points = line[8..].parse()?;
         } else if line.starts_with("Est Distance: ") {
-            est_distance = line[14..].parse()?;
+            metrics.est_distance = line[14..].parse()?;
+        } else if let Some(mode) = line.strip_prefix("Mode: ") {
+            metrics.mode = mode.to_string();
+        } else if let Some(track) = line.strip_prefix("Track: ") {
+            let (pairs, distance) = track.split_once(' ').ok_or("Malformed track line")?;
+            metrics.tracks.push(TrackMetrics {
+                pairs: pairs.parse()?,
+                distance: distance.parse()?,
+            });
+        } else if let Some(anchor) = line.strip_prefix("Anchor: ") {
+            let mut fields = anchor.splitn(3, ' ');
+            let (Some(lat), Some(lng), Some(name)) = (fields.next(), fields.next(), fields.next()) else {
+                return Err("Malformed anchor line".into());
+            };
+            metrics.anchors.push(NamedPoint {
+                name: name.to_string(),
+                point: Point::new(lat.parse()?, lng.parse()?),
+            });
         }
     }
     
-    Ok((seed, points, est_distance))
+    Ok(metrics)
 }
@@ -1,7 +1,11 @@
+use std::borrow::Cow;
 use std::fs::File;
 use std::io::{BufReader, Read};
 use std::string::String;
 
+use crate::error::{ParseError, Position};
+use crate::float::parse_f64;
+use crate::parser::ParseOptions;
 use crate::profile_block;
 
 #[derive(Clone)]
@@ -14,8 +18,8 @@ pub enum Token {
     Colon,
     StringContent(String),
     Number(f64),
-    // Boolean(bool),
-    // Null,
+    Boolean(bool),
+    Null,
 }
 
 impl Token {
@@ -29,85 +33,808 @@ impl Token {
             Token::Colon => ":".to_string(),
             Token::StringContent(s) => s.to_string(),
             Token::Number(n) => n.to_string(),
-            // Token::Boolean(b) => b.to_string(),
-            // Token::Null => "null".to_string(),
+            Token::Boolean(b) => b.to_string(),
+            Token::Null => "null".to_string(),
+        }
+    }
+
+    pub fn to_ref(&self) -> TokenRef<'_> {
+        match self {
+            Token::OpenBrace => TokenRef::OpenBrace,
+            Token::CloseBrace => TokenRef::CloseBrace,
+            Token::OpenBracket => TokenRef::OpenBracket,
+            Token::CloseBracket => TokenRef::CloseBracket,
+            Token::Comma => TokenRef::Comma,
+            Token::Colon => TokenRef::Colon,
+            Token::StringContent(s) => TokenRef::StringContent(Cow::Borrowed(s)),
+            Token::Number(n) => TokenRef::Number(*n),
+            Token::Boolean(b) => TokenRef::Boolean(*b),
+            Token::Null => TokenRef::Null,
+        }
+    }
+
+    pub fn into_ref(self) -> TokenRef<'static> {
+        match self {
+            Token::StringContent(s) => TokenRef::StringContent(Cow::Owned(s)),
+            token => token.to_ref().into_owned(),
         }
     }
 }
 
-pub fn parse_file(file: File) -> Vec<Token> {
-    profile_block!("Parse file");
-    let mut output = Vec::new();
+// A token as the parser consumes it: strings are borrowed from wherever the tokenizer keeps
+// them and only owned when decoding produced new text.
+#[derive(Debug, Clone)]
+pub enum TokenRef<'a> {
+    OpenBrace,
+    CloseBrace,
+    OpenBracket,
+    CloseBracket,
+    Comma,
+    Colon,
+    StringContent(Cow<'a, str>),
+    Number(f64),
+    Boolean(bool),
+    Null,
+}
 
-    let mut string = String::new();
-    let mut in_string = false;
+impl TokenRef<'_> {
+    pub fn into_owned(self) -> TokenRef<'static> {
+        match self {
+            TokenRef::OpenBrace => TokenRef::OpenBrace,
+            TokenRef::CloseBrace => TokenRef::CloseBrace,
+            TokenRef::OpenBracket => TokenRef::OpenBracket,
+            TokenRef::CloseBracket => TokenRef::CloseBracket,
+            TokenRef::Comma => TokenRef::Comma,
+            TokenRef::Colon => TokenRef::Colon,
+            TokenRef::StringContent(s) => TokenRef::StringContent(Cow::Owned(s.into_owned())),
+            TokenRef::Number(n) => TokenRef::Number(n),
+            TokenRef::Boolean(b) => TokenRef::Boolean(b),
+            TokenRef::Null => TokenRef::Null,
+        }
+    }
 
-    let mut digit = String::new();
-    let mut in_digit = false;
+    pub fn describe(&self) -> String {
+        match self {
+            TokenRef::OpenBrace => "'{'".to_string(),
+            TokenRef::CloseBrace => "'}'".to_string(),
+            TokenRef::OpenBracket => "'['".to_string(),
+            TokenRef::CloseBracket => "']'".to_string(),
+            TokenRef::Comma => "','".to_string(),
+            TokenRef::Colon => "':'".to_string(),
+            TokenRef::StringContent(s) => format!("string \"{}\"", s),
+            TokenRef::Number(n) => format!("number {}", n),
+            TokenRef::Boolean(b) => format!("'{}'", b),
+            TokenRef::Null => "'null'".to_string(),
+        }
+    }
+}
 
-    let reader = BufReader::new(file);
-    for byte_result in reader.bytes() {
-        let byte = match byte_result {
-            Ok(byte) => byte,
-            Err(err) => {
-                println!("Error: {}", err);
-                continue;
+#[derive(Clone)]
+pub struct Lexeme {
+    pub token: Token,
+    pub position: Position,
+}
+
+enum Escape {
+    None,
+    Backslash,
+    Unicode { digits: u8, value: u32 },
+}
+
+// A multi-byte UTF-8 sequence in progress; `lower..=upper` is the range allowed for the next
+// byte, which rules out overlong encodings, surrogates and code points above U+10FFFF.
+struct Utf8Sequence {
+    start: usize,
+    remaining: u8,
+    value: u32,
+    lower: u8,
+    upper: u8,
+}
+
+pub struct StringError {
+    pub offset: usize,
+    pub found: String,
+    pub expected: &'static str,
+}
+
+// How a byte outside a token reads in an error message; shared by every tokenizer so their
+// errors match.
+pub(crate) fn describe_byte(byte: u8) -> String {
+    if byte.is_ascii_graphic() || byte == b' ' {
+        format!("'{}'", byte as char)
+    } else {
+        format!("byte 0x{:02X}", byte)
+    }
+}
+
+// Accumulates the bytes between a pair of quotes, decoding escape sequences and UTF-8 as they
+// arrive.
+struct StringDecoder {
+    content: String,
+    escape: Escape,
+    // Offset of the backslash that started the current or most recent escape.
+    escape_start: usize,
+    high_surrogate: Option<u32>,
+    utf8: Option<Utf8Sequence>,
+}
+
+impl StringDecoder {
+    fn new() -> Self {
+        StringDecoder {
+            content: String::new(),
+            escape: Escape::None,
+            escape_start: 0,
+            high_surrogate: None,
+            utf8: None,
+        }
+    }
+
+    fn start_utf8(&mut self, byte: u8, offset: usize) -> Result<(), StringError> {
+        let (remaining, value, lower, upper) = match byte {
+            0xC2..=0xDF => (1, byte & 0x1F, 0x80, 0xBF),
+            0xE0 => (2, byte & 0x0F, 0xA0, 0xBF),
+            0xE1..=0xEC | 0xEE..=0xEF => (2, byte & 0x0F, 0x80, 0xBF),
+            0xED => (2, byte & 0x0F, 0x80, 0x9F),
+            0xF0 => (3, byte & 0x07, 0x90, 0xBF),
+            0xF1..=0xF3 => (3, byte & 0x07, 0x80, 0xBF),
+            0xF4 => (3, byte & 0x07, 0x80, 0x8F),
+            _ => {
+                return Err(StringError {
+                    offset,
+                    found: describe_byte(byte),
+                    expected: "valid UTF-8",
+                });
             }
         };
+        self.utf8 = Some(Utf8Sequence {
+            start: offset,
+            remaining,
+            value: value as u32,
+            lower,
+            upper,
+        });
+        Ok(())
+    }
 
-        match byte {
-            b'{' => output.push(Token::OpenBrace),
-            b'}' => {
-                handle_digit_termination(&mut output, &mut digit, &mut in_digit);
-                output.push(Token::CloseBrace);
+    // Returns true once the closing quote has been consumed. `offset` is the byte's position in
+    // the input, used to locate errors.
+    fn push(&mut self, byte: u8, offset: usize) -> Result<bool, StringError> {
+        if let Some(sequence) = &mut self.utf8 {
+            if !(sequence.lower..=sequence.upper).contains(&byte) {
+                return Err(StringError {
+                    offset: sequence.start,
+                    found: format!("truncated sequence ending in {}", describe_byte(byte)),
+                    expected: "valid UTF-8",
+                });
             }
-            b'[' => output.push(Token::OpenBracket),
-            b']' => {
-                handle_digit_termination(&mut output, &mut digit, &mut in_digit);
-                output.push(Token::CloseBracket);
+            sequence.value = (sequence.value << 6) | (byte & 0x3F) as u32;
+            sequence.remaining -= 1;
+            sequence.lower = 0x80;
+            sequence.upper = 0xBF;
+            if sequence.remaining == 0 {
+                self.content.push(char::from_u32(sequence.value).unwrap());
+                self.utf8 = None;
             }
-            b':' => output.push(Token::Colon),
-            b',' => {
-                handle_digit_termination(&mut output, &mut digit, &mut in_digit);
-                output.push(Token::Comma);
+            return Ok(false);
+        }
+
+        match self.escape {
+            Escape::None => {
+                if self.high_surrogate.is_some() && byte != b'\\' {
+                    return Err(StringError {
+                        offset,
+                        found: describe_byte(byte),
+                        expected: "a \\u low surrogate",
+                    });
+                }
+                match byte {
+                    b'"' => return Ok(true),
+                    b'\\' => {
+                        self.escape = Escape::Backslash;
+                        self.escape_start = offset;
+                    }
+                    0x00..=0x7F => self.content.push(byte as char),
+                    _ => self.start_utf8(byte, offset)?,
+                }
+            }
+            Escape::Backslash => {
+                self.escape = Escape::None;
+                if self.high_surrogate.is_some() && byte != b'u' {
+                    return Err(StringError {
+                        offset,
+                        found: format!("'\\{}'", byte as char),
+                        expected: "a \\u low surrogate",
+                    });
+                }
+                let decoded = match byte {
+                    b'"' => '"',
+                    b'\\' => '\\',
+                    b'/' => '/',
+                    b'b' => '\u{8}',
+                    b'f' => '\u{c}',
+                    b'n' => '\n',
+                    b'r' => '\r',
+                    b't' => '\t',
+                    b'u' => {
+                        self.escape = Escape::Unicode { digits: 0, value: 0 };
+                        return Ok(false);
+                    }
+                    _ => {
+                        return Err(StringError {
+                            offset,
+                            found: format!("escape '\\' followed by {}", describe_byte(byte)),
+                            expected: "one of \\\" \\\\ \\/ \\b \\f \\n \\r \\t \\u",
+                        });
+                    }
+                };
+                self.content.push(decoded);
+            }
+            Escape::Unicode { digits, value } => {
+                let Some(nibble) = (byte as char).to_digit(16) else {
+                    return Err(StringError {
+                        offset,
+                        found: describe_byte(byte),
+                        expected: "a hex digit in \\u escape",
+                    });
+                };
+                let value = (value << 4) | nibble;
+                if digits < 3 {
+                    self.escape = Escape::Unicode { digits: digits + 1, value };
+                    return Ok(false);
+                }
+                self.escape = Escape::None;
+                self.push_code_unit(value)?;
+            }
+        }
+        Ok(false)
+    }
+
+    // Surrogate errors point at the backslash of the offending `\u` escape.
+    fn push_code_unit(&mut self, unit: u32) -> Result<(), StringError> {
+        let error = |expected| StringError {
+            offset: self.escape_start,
+            found: format!("\\u{:04X}", unit),
+            expected,
+        };
+        match (self.high_surrogate.take(), unit) {
+            (Some(high), 0xDC00..=0xDFFF) => {
+                let code_point = 0x10000 + ((high - 0xD800) << 10) + (unit - 0xDC00);
+                self.content.push(char::from_u32(code_point).unwrap());
+            }
+            (Some(_), _) => return Err(error("a \\u low surrogate")),
+            (None, 0xD800..=0xDBFF) => self.high_surrogate = Some(unit),
+            (None, 0xDC00..=0xDFFF) => return Err(error("a \\u high surrogate before it")),
+            (None, _) => self.content.push(char::from_u32(unit).unwrap()),
+        }
+        Ok(())
+    }
+
+    fn take(&mut self) -> String {
+        std::mem::take(&mut self.content)
+    }
+}
+
+// Decodes the raw bytes between a string's quotes, which start at byte `start` of the input.
+pub fn decode_string(content: &[u8], start: usize) -> Result<String, StringError> {
+    let mut decoder = StringDecoder::new();
+    for (i, &byte) in content.iter().enumerate() {
+        decoder.push(byte, start + i)?;
+    }
+    decoder.push(b'"', start + content.len())?;
+    Ok(decoder.take())
+}
+
+// Byte-at-a-time tokenizer. Keeps enough state between calls to `push_byte` to resume in the
+// middle of a string, number or literal.
+pub struct Lexer {
+    position: Position,
+    in_string: bool,
+    string: StringDecoder,
+    digit: String,
+    keyword: String,
+    pending_start: Position,
+    strict: bool,
+}
+
+impl Lexer {
+    pub fn new() -> Self {
+        Lexer::with_options(&ParseOptions::default())
+    }
+
+    pub fn with_options(options: &ParseOptions) -> Self {
+        Lexer {
+            position: Position::start(),
+            in_string: false,
+            string: StringDecoder::new(),
+            digit: String::new(),
+            keyword: String::new(),
+            pending_start: Position::start(),
+            strict: options.strict,
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    pub fn push_byte(&mut self, byte: u8, output: &mut Vec<Lexeme>) -> Result<(), ParseError> {
+        let position = self.position;
+        self.position.advance(byte);
+
+        if self.in_string {
+            if self.strict && byte < 0x20 {
+                return Err(ParseError::new(position, describe_byte(byte), "an escaped control character"));
             }
+            let ended = self.string.push(byte, position.offset).map_err(|err| {
+                ParseError::new(position.back_to(err.offset), err.found, err.expected)
+            })?;
+            if ended {
+                output.push(Lexeme {
+                    token: Token::StringContent(self.string.take()),
+                    position: self.pending_start,
+                });
+                self.in_string = false;
+            }
+            return Ok(());
+        }
+
+        let token = match byte {
+            b'{' => Token::OpenBrace,
+            b'}' => Token::CloseBrace,
+            b'[' => Token::OpenBracket,
+            b']' => Token::CloseBracket,
+            b':' => Token::Colon,
+            b',' => Token::Comma,
             b'"' => {
-                if in_string {
-                    output.push(Token::StringContent(string.clone()));
-                    string.clear();
-                    in_string = false;
-                } else {
-                    in_string = true;
+                self.terminate_pending(output)?;
+                self.in_string = true;
+                self.pending_start = position;
+                return Ok(());
+            }
+            b'a'..=b'z' | b'A'..=b'Z' if self.digit.is_empty() => {
+                if self.keyword.is_empty() {
+                    self.pending_start = position;
                 }
+                self.keyword.push(byte as char);
+                return Ok(());
             }
             b'0'..=b'9' | b'-' | b'.' | b'e' | b'E' | b'+' => {
-                if in_string {
-                    string.push(byte as char);
-                    continue;
+                if !self.keyword.is_empty() {
+                    if self.strict {
+                        return Err(ParseError::new(position, describe_byte(byte), "true, false or null"));
+                    }
+                    // `null1` is a literal and then a number, in that order.
+                    self.terminate_pending(output)?;
+                }
+                if self.digit.is_empty() {
+                    self.pending_start = position;
                 }
-                digit.push(byte as char);
-                in_digit = true;
+                self.digit.push(byte as char);
+                return Ok(());
+            }
+            // Whitespace always ends a number or literal, so `1 2` is two values as in the span lexer.
+            b' ' | b'\t' | b'\n' | b'\r' => {
+                self.terminate_pending(output)?;
+                return Ok(());
             }
             _ => {
-                if in_string {
-                    string.push(byte as char);
+                if self.strict {
+                    return Err(ParseError::new(position, describe_byte(byte), "a JSON token"));
+                }
+                // Skipped, but still a separator.
+                self.terminate_pending(output)?;
+                return Ok(());
+            }
+        };
+
+        self.terminate_pending(output)?;
+        output.push(Lexeme { token, position });
+        Ok(())
+    }
+
+    // Flushes a trailing number or literal and rejects input that ends inside a string.
+    pub fn finish(&mut self, output: &mut Vec<Lexeme>) -> Result<(), ParseError> {
+        if self.in_string {
+            return Err(ParseError::new(self.position, "end of input", "closing '\"' of string"));
+        }
+        self.terminate_pending(output)
+    }
+
+    fn terminate_pending(&mut self, output: &mut Vec<Lexeme>) -> Result<(), ParseError> {
+        if self.strict && !self.digit.is_empty() && !is_rfc8259_number(&self.digit) {
+            return Err(ParseError::new(
+                self.pending_start,
+                format!("'{}'", self.digit),
+                "an RFC 8259 number",
+            ));
+        }
+        handle_digit_termination(output, &mut self.digit, self.pending_start)?;
+        handle_keyword_termination(output, &mut self.keyword, self.pending_start)
+    }
+}
+
+impl Default for Lexer {
+    fn default() -> Self {
+        Lexer::new()
+    }
+}
+
+// `-? (0 | [1-9][0-9]*) (. [0-9]+)? ([eE] [+-]? [0-9]+)?`
+pub fn is_rfc8259_number(candidate: &str) -> bool {
+    let bytes = candidate.as_bytes();
+    let mut i = 0;
+    let digits_from = |i: usize| bytes[i..].iter().take_while(|b| b.is_ascii_digit()).count();
+
+    if bytes.get(i) == Some(&b'-') {
+        i += 1;
+    }
+    match bytes.get(i) {
+        Some(b'0') => i += 1,
+        Some(b'1'..=b'9') => i += digits_from(i),
+        _ => return false,
+    }
+    if bytes.get(i) == Some(&b'.') {
+        let fraction = digits_from(i + 1);
+        if fraction == 0 {
+            return false;
+        }
+        i += 1 + fraction;
+    }
+    if matches!(bytes.get(i), Some(b'e' | b'E')) {
+        i += 1;
+        if matches!(bytes.get(i), Some(b'+' | b'-')) {
+            i += 1;
+        }
+        let exponent = digits_from(i);
+        if exponent == 0 {
+            return false;
+        }
+        i += exponent;
+    }
+    i == bytes.len()
+}
+
+pub fn parse_file(file: File) -> Result<Vec<Lexeme>, ParseError> {
+    parse_file_with(file, &ParseOptions::default())
+}
+
+pub fn parse_file_with(
+    input: impl Read,
+    options: &ParseOptions,
+) -> Result<Vec<Lexeme>, ParseError> {
+    profile_block!("Parse file");
+    let mut output = Vec::new();
+    let mut lexer = Lexer::with_options(options);
+
+    let reader = BufReader::new(input);
+    for byte_result in reader.bytes() {
+        let byte = byte_result.map_err(|err| {
+            ParseError::new(lexer.position(), format!("I/O error ({})", err), "readable input")
+        })?;
+        lexer.push_byte(byte, &mut output)?;
+    }
+    lexer.finish(&mut output)?;
+
+    Ok(output)
+}
+
+// Lexes `reader` on demand: each call to `next` reads only as many bytes as it takes to complete
+// the next token, so nothing but the token in flight is held in memory.
+pub struct TokenStream<R: Read> {
+    bytes: std::io::Bytes<BufReader<R>>,
+    lexer: Lexer,
+    pending: Vec<Lexeme>,
+    done: bool,
+}
+
+impl<R: Read> TokenStream<R> {
+    pub fn new(reader: R, options: &ParseOptions) -> Self {
+        TokenStream {
+            bytes: BufReader::new(reader).bytes(),
+            lexer: Lexer::with_options(options),
+            pending: Vec::with_capacity(2),
+            done: false,
+        }
+    }
+}
+
+impl<R: Read> Iterator for TokenStream<R> {
+    type Item = Result<Lexeme, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // A single byte completes at most two tokens, e.g. the `,` after a number.
+            if !self.pending.is_empty() {
+                return Some(Ok(self.pending.remove(0)));
+            }
+            if self.done {
+                return None;
+            }
+            let result = match self.bytes.next() {
+                Some(Ok(byte)) => self.lexer.push_byte(byte, &mut self.pending),
+                Some(Err(err)) => Err(ParseError::new(
+                    self.lexer.position(),
+                    format!("I/O error ({})", err),
+                    "readable input",
+                )),
+                None => {
+                    self.done = true;
+                    self.lexer.finish(&mut self.pending)
                 }
-                continue;
+            };
+            if let Err(err) = result {
+                self.done = true;
+                return Some(Err(err));
             }
         }
     }
-    return output;
 }
 
-pub fn handle_digit_termination(output: &mut Vec<Token>, digit: &mut String, in_digit: &mut bool) {
-    if !digit.is_empty() {
-        if let Ok(n) = digit.parse() {
-            output.push(Token::Number(n));
-            digit.clear();
-        } else {
-            println!("Error parsing number: {}", digit);
-            digit.clear();
+pub fn handle_digit_termination(
+    output: &mut Vec<Lexeme>,
+    digit: &mut String,
+    position: Position,
+) -> Result<(), ParseError> {
+    if digit.is_empty() {
+        return Ok(());
+    }
+    let Some(n) = parse_f64(digit.as_bytes()) else {
+        return Err(ParseError::new(position, format!("'{}'", digit), "a number"));
+    };
+    output.push(Lexeme {
+        token: Token::Number(n),
+        position,
+    });
+    digit.clear();
+    Ok(())
+}
+
+pub fn handle_keyword_termination(
+    output: &mut Vec<Lexeme>,
+    keyword: &mut String,
+    position: Position,
+) -> Result<(), ParseError> {
+    if keyword.is_empty() {
+        return Ok(());
+    }
+    let token = match keyword.as_str() {
+        "true" => Token::Boolean(true),
+        "false" => Token::Boolean(false),
+        "null" => Token::Null,
+        _ => {
+            return Err(ParseError::new(position, format!("'{}'", keyword), "true, false or null"));
+        }
+    };
+    output.push(Lexeme { token, position });
+    keyword.clear();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{JsonValue, parse_documents, parse_tokens_with};
+    use crate::span_lexer::{parse_spans, tokenize};
+
+    fn documents(input: &str, options: &ParseOptions) -> Vec<String> {
+        parse_documents(TokenStream::new(input.as_bytes(), options), options)
+            .map(|document| format!("{:?}", document.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn whitespace_separates_scalar_documents() {
+        for options in [ParseOptions::default(), ParseOptions { strict: true, ..ParseOptions::default() }] {
+            assert_eq!(documents("1 2 true false", &options), ["Number(1.0)", "Number(2.0)", "Bool(true)", "Bool(false)"]);
+            assert_eq!(documents("null\n-3.5e1\t\"s\"", &options), ["Null", "Number(-35.0)", "String(\"s\")"]);
+        }
+    }
+
+    #[test]
+    fn literals_run_into_numbers_in_input_order() {
+        let options = ParseOptions::default();
+        assert_eq!(documents("null1 true2", &options), ["Null", "Number(1.0)", "Bool(true)", "Number(2.0)"]);
+
+        let tokens = parse_file_with("false-5".as_bytes(), &options).unwrap();
+        let offsets: Vec<usize> = tokens.iter().map(|lexeme| lexeme.position.offset).collect();
+        assert_eq!(offsets, [0, 5]);
+        let strict = ParseOptions { strict: true, ..ParseOptions::default() };
+        assert_eq!(
+            parse_file_with("null1".as_bytes(), &strict).err().unwrap().to_string(),
+            "line 1, column 5 (byte 4): found '1', expected true, false or null"
+        );
+    }
+
+    #[test]
+    fn scalar_and_span_lexers_agree_when_lenient() {
+        let options = ParseOptions::default();
+        for input in [r#"{"x0":1 2,"y0":3}"#, "[1 2 3]", "[true false]", "[1@2]", "[tr ue]", "[1\n\n2]", "[null1]", "[true2, 3]", "null1", "[false-1e2]"] {
+            let scalar = parse_file_with(input.as_bytes(), &options).and_then(|tokens| parse_tokens_with(&tokens, &options));
+            let span = tokenize(input.as_bytes(), &options).and_then(|tokens| parse_spans(input.as_bytes(), &tokens, &options));
+            match (scalar, span) {
+                (Ok(scalar), Ok(span)) => assert_eq!(scalar, span, "{input}"),
+                (Err(scalar), Err(span)) => assert_eq!(scalar.to_string(), span.to_string(), "{input}"),
+                (scalar, span) => panic!("{input}: scalar {:?}, span {:?}", scalar, span),
+            }
+        }
+    }
+
+    fn decoded(content: &str) -> Result<String, (usize, String, &'static str)> {
+        decode_string(content.as_bytes(), 0).map_err(|err| (err.offset, err.found, err.expected))
+    }
+
+    // Offset of the error the scalar and span lexers report for `content` between quotes.
+    fn lexer_error_offsets(content: &str) -> (usize, usize) {
+        let input = format!("\"{}\"", content);
+        let options = ParseOptions::default();
+        let scalar = parse_file_with(input.as_bytes(), &options).err().unwrap();
+        let tokens = tokenize(input.as_bytes(), &options).unwrap();
+        let span = tokens[0].resolve(input.as_bytes()).err().unwrap();
+        (scalar.position.offset, span.position.offset)
+    }
+
+    #[test]
+    fn escapes_decode() {
+        for (content, expected) in [
+            (r#"\"\\\/\b\f\n\r\t"#, "\"\\/\u{8}\u{c}\n\r\t"),
+            (r"a\tb\\n", "a\tb\\n"),
+            (r"\u0041\u00e9\u20AC\u0000", "A\u{e9}\u{20ac}\0"),
+            (r"\uD83D\uDE00 and \ud83d\ude00", "\u{1f600} and \u{1f600}"),
+            (r"\uDBFF\uDFFF\uD800\uDC00", "\u{10ffff}\u{10000}"),
+            ("no escapes \u{e9}", "no escapes \u{e9}"),
+            ("", ""),
+        ] {
+            assert_eq!(decoded(content), Ok(expected.to_string()), "{content}");
+        }
+    }
+
+    #[test]
+    fn invalid_escapes_and_surrogates_are_errors() {
+        const LOW: &str = "a \\u low surrogate";
+        const HIGH: &str = "a \\u high surrogate before it";
+        const ESCAPE: &str = "one of \\\" \\\\ \\/ \\b \\f \\n \\r \\t \\u";
+        const HEX: &str = "a hex digit in \\u escape";
+        for (content, offset, found, expected) in [
+            (r"ab\uDE00", 2, r"\uDE00", HIGH),
+            (r"\uDE00\uD83D", 0, r"\uDE00", HIGH),
+            (r"\uD83D", 6, "'\"'", LOW),
+            (r"\uD83Dx", 6, "'x'", LOW),
+            (r"\uD83D\n", 7, r"'\n'", LOW),
+            (r"\uD83D\uD83D", 6, r"\uD83D", LOW),
+            (r"\uD83DA", 6, "'A'", LOW),
+            (r"a\x", 2, r"escape '\' followed by 'x'", ESCAPE),
+            (r"\U0041", 1, r"escape '\' followed by 'U'", ESCAPE),
+            (r"\u12G4", 4, "'G'", HEX),
+            (r"\u12", 4, "'\"'", HEX),
+        ] {
+            assert_eq!(decoded(content), Err((offset, found.to_string(), expected)), "{content}");
+            // Both lexers report it at the same byte, one past the opening quote.
+            assert_eq!(lexer_error_offsets(content), (offset + 1, offset + 1), "{content}");
+        }
+    }
+
+    // Parses `["<content>"]` with the scalar and span lexers in both modes, requiring the same
+    // result from each.
+    fn parse_string_both_ways(content: &[u8]) -> Result<String, String> {
+        let mut input = b"[\"".to_vec();
+        input.extend_from_slice(content);
+        input.extend_from_slice(b"\"]");
+        let mut results = Vec::new();
+        for strict in [false, true] {
+            let options = ParseOptions { strict, ..ParseOptions::default() };
+            let scalar = parse_file_with(&input[..], &options).and_then(|tokens| parse_tokens_with(&tokens, &options));
+            let span = tokenize(&input, &options).and_then(|tokens| parse_spans(&input, &tokens, &options));
+            for result in [scalar, span] {
+                results.push(match result {
+                    Ok(JsonValue::Array(values)) => match &values[..] {
+                        [JsonValue::String(s)] => Ok(s.clone()),
+                        values => panic!("{:?}: parsed as {:?}", content, values),
+                    },
+                    Ok(value) => panic!("{:?}: parsed as {:?}", content, value),
+                    Err(err) => Err(err.to_string()),
+                });
+            }
+        }
+        assert!(results.iter().all(|result| *result == results[0]), "{:?}: {:?}", content, results);
+        results.remove(0)
+    }
+
+    #[test]
+    fn valid_utf8_is_kept() {
+        for content in ["\u{e9}", "\u{20ac}", "\u{1f600}", "\u{7ff}\u{800}", "\u{d7ff}\u{e000}", "\u{fffd}\u{ffff}", "\u{10000}\u{10ffff}"] {
+            assert_eq!(parse_string_both_ways(content.as_bytes()), Ok(content.to_string()));
+        }
+    }
+
+    #[test]
+    fn invalid_utf8_is_rejected_alike_by_both_lexers() {
+        // Byte 2 is the first byte inside the string.
+        for (content, offset, found) in [
+            (&b"\xC0\x80"[..], 2, "byte 0xC0"),
+            (b"\xC1\xBF", 2, "byte 0xC1"),
+            (b"ab\xE0\x80\x80", 4, "truncated sequence ending in byte 0x80"),
+            (b"\xE0\x9F\xBF", 2, "truncated sequence ending in byte 0x9F"),
+            (b"\xF0\x80\x80\x80", 2, "truncated sequence ending in byte 0x80"),
+            (b"\xF0\x8F\xBF\xBF", 2, "truncated sequence ending in byte 0x8F"),
+            (b"\xED\xA0\x80", 2, "truncated sequence ending in byte 0xA0"),
+            (b"\xED\xBF\xBF", 2, "truncated sequence ending in byte 0xBF"),
+            (b"\xF4\x90\x80\x80", 2, "truncated sequence ending in byte 0x90"),
+            (b"\xF5\x80\x80\x80", 2, "byte 0xF5"),
+            (b"x\xFE", 3, "byte 0xFE"),
+            (b"\xFF", 2, "byte 0xFF"),
+            (b"\x80", 2, "byte 0x80"),
+            (b"\xC3", 2, "truncated sequence ending in '\"'"),
+            (b"\xE2\x82", 2, "truncated sequence ending in '\"'"),
+            (b"\xF0\x9F\x98", 2, "truncated sequence ending in '\"'"),
+            (b"\xE2\x82a", 2, "truncated sequence ending in 'a'"),
+            (b"\xC3\xA9\xE2\x82\\n", 4, "truncated sequence ending in '\\'"),
+        ] {
+            let expected = format!("line 1, column {} (byte {}): found {}, expected valid UTF-8", offset + 1, offset, found);
+            assert_eq!(parse_string_both_ways(content), Err(expected), "{:?}", content);
+        }
+    }
+
+    #[test]
+    fn input_ending_inside_a_utf8_sequence_is_rejected_alike() {
+        let options = ParseOptions::default();
+        for input in [&b"\"\xE2"[..], b"\"\xE2\x82", b"\"\xF0\x9F\x98", b"[\"\xC3"] {
+            let scalar = parse_file_with(input, &options).err().unwrap();
+            let span = tokenize(input, &options).err().unwrap();
+            assert_eq!(scalar.to_string(), span.to_string(), "{:?}", input);
+            assert_eq!(span.position.offset, input.len(), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn rfc8259_numbers() {
+        for number in ["0", "-0", "7", "-12", "0.5", "10.25", "1e5", "1E+5", "2.5e-3", "0e0", "-0.0E-0"] {
+            assert!(is_rfc8259_number(number), "{number}");
+        }
+        for number in ["", "-", "+1", "01", "-01", "00", "1.", ".5", "-.5", "1.e2", "1e", "1e+", "1e-", "--1", "1-", "1e5.0", "1ee5", "0x1", "1.2.3"] {
+            assert!(!is_rfc8259_number(number), "{number}");
+        }
+    }
+
+    #[test]
+    fn strict_mode_rejects_what_lenient_mode_reads_past() {
+        const NUMBER: &str = "an RFC 8259 number";
+        const CONTROL: &str = "an escaped control character";
+        // Input, strict error offset, found and expected, and valid JSON that lenient mode reads
+        // the input as, or `None` where lenient mode fails too.
+        for (input, offset, found, expected, lenient) in [
+            ("01", 0, "'01'", NUMBER, Some("1")),
+            ("-01", 0, "'-01'", NUMBER, Some("-1")),
+            ("[1.]", 1, "'1.'", NUMBER, Some("[1]")),
+            (".5", 0, "'.5'", NUMBER, Some("0.5")),
+            ("+1", 0, "'+1'", NUMBER, Some("1")),
+            ("-", 0, "'-'", NUMBER, None),
+            ("--1", 0, "'--1'", NUMBER, None),
+            ("1e", 0, "'1e'", NUMBER, None),
+            ("[1,]", 3, "']'", "a value", Some("[1]")),
+            ("{\"a\":1,}", 7, "'}'", "a string key", Some("{\"a\":1}")),
+            ("[1,,2]", 3, "','", "a value", Some("[1,2]")),
+            ("[1 2]", 3, "number 2", "',' or ']'", Some("[1]")),
+            ("{\"a\" 1}", 5, "number 1", "':'", None),
+            ("[1@]", 2, "'@'", "a JSON token", Some("[1]")),
+            ("\"a\u{1}b\"", 2, "byte 0x01", CONTROL, Some("\"a\\u0001b\"")),
+            ("\"tab\there\"", 4, "byte 0x09", CONTROL, Some("\"tab\\there\"")),
+            ("[1] 2", 4, "number 2", "end of input", Some("[1]")),
+            ("{}}", 2, "'}'", "end of input", Some("{}")),
+        ] {
+            let parse = |input: &str, strict| {
+                let options = ParseOptions { strict, ..ParseOptions::default() };
+                let scalar = parse_file_with(input.as_bytes(), &options).and_then(|tokens| parse_tokens_with(&tokens, &options));
+                let span = tokenize(input.as_bytes(), &options).and_then(|tokens| parse_spans(input.as_bytes(), &tokens, &options));
+                [scalar, span]
+            };
+            for strict in parse(input, true) {
+                let err = strict.unwrap_err();
+                assert_eq!((err.position.offset, &err.found[..], &err.expected[..]), (offset, found, expected), "{input}");
+            }
+            let expected = lenient.map(|equivalent| parse(equivalent, true)[0].clone().unwrap());
+            for result in parse(input, false) {
+                assert_eq!(result.ok(), expected, "{input}");
+            }
         }
     }
-    *in_digit = false;
 }
@@ -1,17 +1,51 @@
+mod anchors;
+mod arena;
+mod error;
+mod events;
+mod float;
+mod from_json;
+mod float_table;
 mod generator;
+mod geodesy;
 mod haversine;
 mod lexer;
+mod object;
+mod pair_events;
+mod pair_schema;
 mod parser;
 mod profiler;
+mod push_parser;
+mod query;
+mod reader;
+mod serializer;
+mod span_lexer;
+mod structural;
 mod timer;
 
-use clap::{Parser, Subcommand};
-use generator::{Pair, Pairs};
-use haversine::{read_run_metrics, reference_haversine, save_run_metrics};
-use lexer::parse_file;
-use parser::{JsonValue, parse_tokens};
-use rand::SeedableRng;
-use rand::rngs::SmallRng;
+use clap::{Parser, Subcommand, ValueEnum};
+use anchors::load_anchors;
+use arena::parse_arena;
+use generator::{
+    AnchorSampling, DistanceDistribution, PairMode, PairStream, Pairs, TrajectoryConfig,
+};
+use haversine::{
+    RunMetrics, TrackMetrics, read_distances_from_file, read_run_metrics, reference_haversine,
+    save_run_metrics,
+};
+use error::ParseError;
+use events::{Event, parse_events};
+use from_json::{FromJson, FromJsonError};
+use lexer::{TokenStream, parse_file_with};
+use pair_events::PairDistances;
+use pair_schema::parse_pair_columns;
+use parser::{DuplicateKeys, JsonValue, ParseOptions, parse_documents, parse_stream, parse_tokens_with};
+use query::QueryNode;
+use push_parser::{PushParser, PushValueParser};
+use reader::FileBuffer;
+use serializer::{WriteOptions, write_json};
+use span_lexer::{parse_spans, span_events, tokenize};
+use structural::tokenize_simd;
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -25,6 +59,30 @@ struct Cli {
     command: Option<Command>,
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum LexerKind {
+    /// Byte-at-a-time lexer reading the file through a BufReader
+    Scalar,
+    /// Zero-copy span tokenizer over the whole file loaded into memory
+    Span,
+    /// Byte-at-a-time lexer feeding the parser as tokens arrive, without a token buffer
+    Stream,
+    /// Span tokenizer that first finds token boundaries with SSE2/AVX2 bitmasks
+    Simd,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ReadStrategy {
+    /// A single read_to_end into a buffer pre-sized from the file's metadata
+    ReadToEnd,
+    /// Repeated reads of --chunk-size bytes appended to a buffer
+    Chunked,
+    /// Memory-map the file with sequential and will-need madvise hints (Unix only)
+    Mmap,
+    /// O_DIRECT reads into a block-aligned buffer, bypassing the page cache (Linux only)
+    Direct,
+}
+
 #[derive(Subcommand, Debug)]
 enum Command {
     /// Generate random coordinate pairs and save to JSON file
@@ -38,6 +96,33 @@ enum Command {
         // Binary output filepath for distance pairs
         #[arg(help = "Path where the generated distance pairs will be saved")]
         distance_output: String,
+        /// Number of coordinate pairs to generate
+        #[arg(long, default_value_t = 10000000)]
+        num_pairs: u64,
+        /// Sample pair distances (km) from a distribution instead of the uniform coordinate box
+        #[arg(
+            long,
+            help = "Distance distribution in km: uniform:MIN,MAX, log-uniform:MIN,MAX or histogram:MIN-MAX=WEIGHT,..."
+        )]
+        distance_distribution: Option<DistanceDistribution>,
+        /// Simulate this many GPS-like tracks and emit consecutive fixes as pairs
+        #[arg(long, conflicts_with = "distance_distribution")]
+        tracks: Option<u64>,
+        /// Mean distance (km) between consecutive fixes of a track
+        #[arg(long, default_value_t = 0.05, requires = "tracks")]
+        step_km: f64,
+        /// Relative noise applied to each step length (0..=1)
+        #[arg(long, default_value_t = 0.2, requires = "tracks")]
+        speed_noise: f64,
+        /// Maximum heading change (degrees) between consecutive fixes
+        #[arg(long, default_value_t = 10.0, requires = "tracks")]
+        heading_noise: f64,
+        /// Sample pairs between named anchor points from a CSV (name,lat,lng) or JSON file
+        #[arg(long, conflicts_with_all = ["distance_distribution", "tracks"])]
+        anchors: Option<String>,
+        /// Maximum distance (km) each sampled endpoint is moved away from its anchor
+        #[arg(long, default_value_t = 0.0, requires = "anchors")]
+        jitter_km: f64,
     },
     /// Calculate haversine distances from coordinate pairs in a JSON file
     Calculate {
@@ -47,6 +132,78 @@ enum Command {
         /// Metrics file containing expected values for validation
         #[arg(help = "Path to metrics file with expected distance values")]
         metrics_file: String,
+        /// Binary distance file written by generate, for pair-by-pair verification
+        #[arg(long)]
+        distances: Option<String>,
+        /// Reject input that is not strictly valid RFC 8259 JSON instead of skipping over it
+        #[arg(long)]
+        strict: bool,
+        /// Tokenizer used to read the input
+        #[arg(long, value_enum, default_value_t = LexerKind::Scalar)]
+        lexer: LexerKind,
+        /// Load the whole file into memory this way before lexing (scalar and stream otherwise
+        /// read it lazily, span and simd with read-to-end)
+        #[arg(long, value_enum)]
+        read: Option<ReadStrategy>,
+        /// Bytes per read for --read chunked and direct, and per chunk fed with --push
+        #[arg(long, default_value_t = 1 << 20)]
+        chunk_size: usize,
+        /// What to do with repeated object keys: error, first, last or keep-all
+        #[arg(long, default_value_t = DuplicateKeys::LastWins)]
+        duplicate_keys: DuplicateKeys,
+        /// Treat the input as concatenated JSON documents and process the pairs of every one
+        #[arg(long, conflicts_with_all = ["lexer", "read"])]
+        documents: bool,
+        /// Read {"pairs":[...]} files with a parser specialised for that layout, falling back to
+        /// the generic parser when the file is laid out differently
+        #[arg(long, conflicts_with = "documents")]
+        schema: bool,
+        /// Feed the input to the incremental push parser in --chunk-size pieces, as it would
+        /// arrive from a pipe or socket; an input file of `-` reads standard input
+        #[arg(long, conflicts_with_all = ["lexer", "read", "schema"])]
+        push: bool,
+    },
+    /// Regenerate the pairs from a metrics file's seed and compare them with a JSON data file
+    Verify {
+        /// Input file containing coordinate pairs in JSON format
+        #[arg(help = "Path to JSON file containing the coordinate pairs to verify")]
+        input_file: String,
+        /// Metrics file written alongside the data file by generate
+        #[arg(help = "Path to metrics file holding the seed and generation parameters")]
+        metrics_file: String,
+    },
+    /// Re-serialize a JSON file with consistent escaping, number formatting and layout
+    Format {
+        /// JSON file to reformat
+        input_file: String,
+        /// Write here instead of standard output
+        #[arg(long)]
+        output: Option<String>,
+        /// Spaces per nesting level
+        #[arg(long, default_value_t = 2)]
+        indent: usize,
+        /// Write everything on a single line
+        #[arg(long, conflicts_with = "indent")]
+        compact: bool,
+        /// Write object keys in sorted order
+        #[arg(long)]
+        sort_keys: bool,
+        /// What to do with repeated object keys: error, first, last or keep-all
+        #[arg(long, default_value_t = DuplicateKeys::LastWins)]
+        duplicate_keys: DuplicateKeys,
+    },
+    /// Print the values at a path such as `pairs[*].x0`, one compact JSON value per line
+    Query {
+        /// JSON file to inspect
+        input_file: String,
+        /// Dotted path with `[index]`, `[*]` and `.*` segments, or a JSON Pointer with --pointer
+        path: String,
+        /// Treat the path as an RFC 6901 JSON Pointer such as `/pairs/42/x0`
+        #[arg(long)]
+        pointer: bool,
+        /// Load the file into the compact arena DOM and report its memory usage on stderr
+        #[arg(long)]
+        arena: bool,
     },
 }
 
@@ -60,46 +217,168 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             output_file,
             metrics_output,
             distance_output,
+            num_pairs,
+            distance_distribution,
+            tracks,
+            step_km,
+            speed_noise,
+            heading_noise,
+            anchors,
+            jitter_km,
         }) => {
-            generate_pairs(output_file, metrics_output, distance_output)?;
+            let mode = if let Some(distribution) = distance_distribution {
+                PairMode::Distance(distribution.clone())
+            } else if let Some(tracks) = tracks {
+                PairMode::Trajectory(TrajectoryConfig {
+                    tracks: *tracks,
+                    step_km: *step_km,
+                    speed_noise: *speed_noise,
+                    heading_noise: *heading_noise,
+                })
+            } else if let Some(anchors) = anchors {
+                PairMode::Anchors(AnchorSampling {
+                    source: anchors.clone(),
+                    anchors: load_anchors(anchors)?,
+                    jitter_km: *jitter_km,
+                })
+            } else {
+                PairMode::Uniform
+            };
+            generate_pairs(output_file, metrics_output, distance_output, *num_pairs, &mode)?;
         }
         Some(Command::Calculate {
             input_file,
             metrics_file,
+            distances: distances_file,
+            strict,
+            lexer,
+            read,
+            chunk_size,
+            duplicate_keys,
+            documents,
+            schema,
+            push,
         }) => {
-            let file = {
-                profile_block!("ReadEntireFile");
-                std::fs::File::open(input_file)?
+            let options = ParseOptions {
+                strict: *strict,
+                duplicate_keys: *duplicate_keys,
             };
-            let (seed, points, est_distance) = read_run_metrics(metrics_file)?;
-
-            let tokens = parse_file(file);
+            let metrics = read_run_metrics(metrics_file)?;
+            let est_distance = metrics.est_distance;
 
-            println!("Seed: {}", seed);
-            println!("Points: {}", points);
+            println!("Seed: {}", metrics.seed);
+            println!("Points: {}", metrics.points);
             println!("Est Distance: {}", est_distance);
 
-            let json = parse_tokens(&tokens);
-            if let Some(json_value) = json {
-                let distances: Vec<f64> = calculate_pairs(json_value);
-
-                let actual_distance: f64 = {
-                    profile_block!("SumHaversineDistances");
-                    distances.iter().sum()
-                };
-
-                println!("Actual Distance: {}", actual_distance);
-                println!("Expected Distance: {}", est_distance);
-                println!(
-                    "Distance Difference: {}",
-                    (actual_distance - est_distance).abs()
-                );
+            let distances: Vec<f64> = if *push {
+                push_pair_distances(input_file, *chunk_size, *documents, &options)?
+            } else if *documents {
+                // Each document is processed and dropped before the next one is parsed.
+                let file = std::fs::File::open(input_file)?;
+                let mut distances = Vec::new();
+                for document in parse_documents(TokenStream::new(file, &options), &options) {
+                    let document = document.inspect_err(|err| report_parse_error(input_file, err))?;
+                    distances.extend(calculate_pairs(&document).map_err(|err| reading_error(input_file, err))?);
+                }
+                distances
+            } else if *schema {
+                schema_pair_distances(input_file, *lexer, *read, *chunk_size, &options)?
             } else {
-                println!("Error parsing JSON");
+                stream_pair_distances(input_file, *lexer, *read, *chunk_size, &options)?
+            };
+
+            let actual_distance: f64 = {
+                profile_block!("SumHaversineDistances");
+                distances.iter().sum()
+            };
+
+            println!("Actual Distance: {}", actual_distance);
+            println!("Expected Distance: {}", est_distance);
+            println!(
+                "Distance Difference: {}",
+                (actual_distance - est_distance).abs()
+            );
+
+            if let Some(distances_file) = distances_file {
+                let expected = read_distances_from_file(distances_file)?;
+                verify_pairwise(&distances, &expected);
+            }
+            if !metrics.tracks.is_empty() {
+                verify_tracks(&distances, &metrics.tracks);
             }
 
             profiler::KEEPER.report();
         }
+        Some(Command::Verify {
+            input_file,
+            metrics_file,
+        }) => {
+            let (metrics, mode) = read_generation(metrics_file)?;
+
+            println!("Seed: {}", metrics.seed);
+            println!("Points: {}", metrics.points);
+            println!("Mode: {}", mode.describe());
+
+            let json = parse_input(input_file, LexerKind::Scalar, None, 0, &ParseOptions::default())?;
+            let stream = PairStream::new(&mode, metrics.seed, metrics.points, 6372.8);
+            let verified = verify_pairs(&json, stream)?;
+
+            println!("Verified {} pairs: data file matches seed {}", verified, metrics.seed);
+        }
+        Some(Command::Format {
+            input_file,
+            output,
+            indent,
+            compact,
+            sort_keys,
+            duplicate_keys,
+        }) => {
+            let options = ParseOptions {
+                strict: true,
+                duplicate_keys: *duplicate_keys,
+            };
+            let json = parse_input(input_file, LexerKind::Span, None, 0, &options)?;
+
+            let write_options = WriteOptions {
+                indent: (!compact).then_some(*indent),
+                sort_keys: *sort_keys,
+            };
+            let mut writer: Box<dyn Write> = match output {
+                Some(path) => Box::new(BufWriter::new(std::fs::File::create(path)?)),
+                None => Box::new(BufWriter::new(std::io::stdout().lock())),
+            };
+            write_json(&mut writer, &json, &write_options)?;
+            writeln!(writer)?;
+            writer.flush()?;
+        }
+        Some(Command::Query {
+            input_file,
+            path,
+            pointer,
+            arena,
+        }) => {
+            let options = ParseOptions::default();
+            let mut writer = BufWriter::new(std::io::stdout().lock());
+            let write_options = WriteOptions::default();
+
+            if *arena {
+                let file = std::fs::File::open(input_file)?;
+                let arena = parse_arena(TokenStream::new(file, &options), &options)
+                    .inspect_err(|err| report_parse_error(input_file, err))?;
+                eprintln!("{}", arena.memory_usage());
+                write_query_matches(arena.root(), input_file, path, *pointer, |node| {
+                    write_json(&mut writer, &node.to_json_value(), &write_options)?;
+                    writeln!(writer)
+                })?;
+            } else {
+                let json = parse_input(input_file, LexerKind::Span, None, 0, &options)?;
+                write_query_matches(&json, input_file, path, *pointer, |node| {
+                    write_json(&mut writer, node, &write_options)?;
+                    writeln!(writer)
+                })?;
+            }
+            writer.flush()?;
+        }
         None => {
             println!("Haversine Data Generator");
             println!("========================");
@@ -107,13 +386,36 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             println!("No command specified. Use --help for detailed usage information.");
             println!();
             println!("Available commands:");
-            println!("  generate <output_file>");
+            println!("  generate <output_file> <metrics_output> <distance_output>");
             println!("    Generate random haversine coordinate pairs and save to JSON file");
             println!("    Creates both data file and metrics file for validation");
+            println!("    Use --distance-distribution to control the spread of pair distances");
+            println!("    Use --tracks to emit consecutive fixes along simulated GPS tracks");
+            println!("    Use --anchors to sample pairs between named points from a CSV or JSON file");
             println!();
             println!("  calculate <input_file> <metrics_file>");
             println!("    Calculate haversine distances from existing JSON coordinate pairs");
             println!("    Compares calculated distances against expected values from metrics");
+            println!("    Use --distances to also compare every pair against the binary distance file");
+            println!("    Use --strict to reject anything that is not valid RFC 8259 JSON");
+            println!("    Use --lexer span to tokenize the file in memory without per-token allocations");
+            println!("    Use --lexer simd to find token boundaries with SSE2/AVX2 before tokenizing");
+            println!("    Use --lexer stream to parse tokens as they are read instead of buffering them all");
+            println!("    Use --duplicate-keys error|first|last|keep-all to choose how repeated keys are handled");
+            println!("    Use --documents to read a file of concatenated JSON documents");
+            println!("    Use --read read-to-end|chunked|mmap|direct to load the file up front and report read bandwidth");
+            println!("    Use --push to feed the file (or - for standard input) to the incremental push parser in chunks");
+            println!("    Use --schema to parse {{\"pairs\":[...]}} files with a specialised parser, falling back if they differ");
+            println!();
+            println!("  verify <input_file> <metrics_file>");
+            println!("    Regenerate pairs from the metrics seed and report the first divergence");
+            println!();
+            println!("  format <input_file>");
+            println!("    Re-serialize a JSON file; use --compact, --indent and --sort-keys to control layout");
+            println!();
+            println!("  query <input_file> <path>");
+            println!("    Print the values at a path such as pairs[*].x0, or at a JSON Pointer with --pointer");
+            println!("    Use --arena to load the file into the compact arena DOM and report its memory usage");
             println!();
             println!("Examples:");
             println!("  {} generate data.json", env!("CARGO_PKG_NAME"));
@@ -132,83 +434,547 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn calculate_pairs(json: JsonValue) -> Vec<f64> {
-    profile_block!("Calculate pairs");
-    let JsonValue::Object(map) = json else {
-        return Vec::new();
+fn parse_input(
+    input_file: &str,
+    lexer: LexerKind,
+    read: Option<ReadStrategy>,
+    chunk_size: usize,
+    options: &ParseOptions,
+) -> Result<JsonValue, Box<dyn std::error::Error>> {
+    let json = match (lexer, read) {
+        (LexerKind::Scalar, None) => {
+            let file = std::fs::File::open(input_file).map_err(|err| reading_error(input_file, err))?;
+            parse_file_with(file, options).and_then(|tokens| parse_tokens_with(&tokens, options))
+        }
+        (LexerKind::Stream, None) => {
+            let file = std::fs::File::open(input_file).map_err(|err| reading_error(input_file, err))?;
+            parse_stream(TokenStream::new(file, options), options)
+        }
+        (lexer, read) => {
+            let strategy = read.unwrap_or(ReadStrategy::ReadToEnd);
+            let buffer = read_input(input_file, strategy, chunk_size).map_err(|err| reading_error(input_file, err))?;
+            match lexer {
+                LexerKind::Scalar => parse_file_with(&buffer[..], options)
+                    .and_then(|tokens| parse_tokens_with(&tokens, options)),
+                LexerKind::Stream => parse_stream(TokenStream::new(&buffer[..], options), options),
+                LexerKind::Span => tokenize(&buffer, options)
+                    .and_then(|tokens| parse_spans(&buffer, &tokens, options)),
+                LexerKind::Simd => tokenize_simd(&buffer, options)
+                    .and_then(|tokens| parse_spans(&buffer, &tokens, options)),
+            }
+        }
     };
-    let pairs_array = {
-        profile_block!("ParseHaversinePairs");
-        map.get("pairs").expect("Error getting pairs array")
+
+    Ok(json.inspect_err(|err| report_parse_error(input_file, err))?)
+}
+
+// Passes each node `path` selects under `root` to `write_node`; with `pointer` the path is a
+// JSON Pointer, which must match.
+fn write_query_matches<'a, N: QueryNode<'a>>(
+    root: N,
+    input_file: &str,
+    path: &str,
+    pointer: bool,
+    mut write_node: impl FnMut(N) -> std::io::Result<()>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if pointer {
+        let node = root
+            .pointer(path)
+            .ok_or_else(|| format!("No value at {} in {}", path, input_file))?;
+        write_node(node)?;
+        return Ok(());
+    }
+    let nodes = root.query(path).map_err(|err| format!("Invalid path {}: {}", path, err))?;
+    for node in nodes {
+        write_node(node)?;
+    }
+    Ok(())
+}
+
+// Same choice of lexer and read strategy as `parse_input`, but the pairs are taken from parse
+// events as they arrive, so no document tree is built.
+fn stream_pair_distances(
+    input_file: &str,
+    lexer: LexerKind,
+    read: Option<ReadStrategy>,
+    chunk_size: usize,
+    options: &ParseOptions,
+) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    let mut pairs = PairDistances::new(6372.8, options.duplicate_keys);
+    let report = |err: &ParseError| report_parse_error(input_file, err);
+
+    // The byte-at-a-time lexers both pull tokens on demand here; collecting them first would
+    // hold every token of the file in memory before the first event.
+    match (lexer, read) {
+        (LexerKind::Scalar | LexerKind::Stream, None) => {
+            let file = std::fs::File::open(input_file)?;
+            push_pair_events(input_file, parse_events(TokenStream::new(file, options), options), &mut pairs)?;
+        }
+        (lexer, read) => {
+            let strategy = read.unwrap_or(ReadStrategy::ReadToEnd);
+            let buffer = read_input(input_file, strategy, chunk_size)?;
+            let pushed = match lexer {
+                LexerKind::Scalar | LexerKind::Stream => {
+                    let tokens = TokenStream::new(&buffer[..], options);
+                    push_pair_events(input_file, parse_events(tokens, options), &mut pairs)
+                }
+                LexerKind::Span => {
+                    let tokens = tokenize(&buffer, options).inspect_err(report)?;
+                    push_pair_events(input_file, span_events(&buffer, &tokens, options), &mut pairs)
+                }
+                LexerKind::Simd => {
+                    let tokens = tokenize_simd(&buffer, options).inspect_err(report)?;
+                    push_pair_events(input_file, span_events(&buffer, &tokens, options), &mut pairs)
+                }
+            };
+            pushed?;
+        }
+    }
+
+    Ok(pairs.finish().map_err(|err| reading_error(input_file, err))?)
+}
+
+// Tries the parser specialised for the pairs layout first; a file laid out any other way is
+// reported and handed to `stream_pair_distances`.
+fn schema_pair_distances(
+    input_file: &str,
+    lexer: LexerKind,
+    read: Option<ReadStrategy>,
+    chunk_size: usize,
+    options: &ParseOptions,
+) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    let buffer = read_input(input_file, read.unwrap_or(ReadStrategy::ReadToEnd), chunk_size)?;
+    match parse_pair_columns(&buffer) {
+        Ok(columns) => Ok(columns.distances(6372.8)),
+        Err(err) => {
+            println!("{} does not match the pairs schema at {}", input_file, err);
+            println!("Falling back to the generic parser");
+            drop(buffer);
+            stream_pair_distances(input_file, lexer, read, chunk_size, options)
+        }
+    }
+}
+
+// Reads the input `chunk_size` bytes at a time and hands each chunk to the push parser, taking
+// pairs from its events or, with `documents`, from each complete root value.
+fn push_pair_distances(
+    input_file: &str,
+    chunk_size: usize,
+    documents: bool,
+    options: &ParseOptions,
+) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    profile_block!("Push parse");
+    let mut input: Box<dyn Read> = if input_file == "-" {
+        Box::new(std::io::stdin().lock())
+    } else {
+        Box::new(std::fs::File::open(input_file)?)
     };
-    let JsonValue::Array(pairs_array) = pairs_array else {
-        return Vec::new();
+    let report = |err: &ParseError| report_parse_error(input_file, err);
+    let mut chunk = vec![0u8; chunk_size.max(1)];
+
+    if documents {
+        let mut parser = PushValueParser::new(options);
+        let mut values = Vec::new();
+        let mut distances = Vec::new();
+        loop {
+            let count = read_chunk(&mut input, &mut chunk)?;
+            if count == 0 {
+                parser.finish(&mut values).inspect_err(report)?;
+            } else {
+                parser.feed(&chunk[..count], &mut values).inspect_err(report)?;
+            }
+            for document in values.drain(..) {
+                distances.extend(calculate_pairs(&document).map_err(|err| reading_error(input_file, err))?);
+            }
+            if count == 0 {
+                return Ok(distances);
+            }
+        }
+    }
+
+    let mut parser = PushParser::new(options, false);
+    let mut events = Vec::new();
+    let mut pairs = PairDistances::new(6372.8, options.duplicate_keys);
+    loop {
+        let count = read_chunk(&mut input, &mut chunk)?;
+        if count == 0 {
+            parser.finish(&mut events).inspect_err(report)?;
+        } else {
+            parser.feed(&chunk[..count], &mut events).inspect_err(report)?;
+        }
+        for event in events.drain(..) {
+            pairs.push(event).map_err(|err| reading_error(input_file, err))?;
+        }
+        if count == 0 {
+            return Ok(pairs.finish().map_err(|err| reading_error(input_file, err))?);
+        }
+    }
+}
+
+// One `read` into `chunk`, retried if interrupted; 0 means end of input.
+fn read_chunk(input: &mut impl Read, chunk: &mut [u8]) -> std::io::Result<usize> {
+    loop {
+        match input.read(chunk) {
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            result => return result,
+        }
+    }
+}
+
+fn push_pair_events<'a>(
+    input_file: &str,
+    events: impl Iterator<Item = Result<Event<'a>, ParseError>>,
+    pairs: &mut PairDistances,
+) -> Result<(), Box<dyn std::error::Error>> {
+    profile_block!("Calculate pairs");
+    for event in events {
+        let event = event.inspect_err(|err| report_parse_error(input_file, err))?;
+        pairs.push(event).map_err(|err| reading_error(input_file, err))?;
+    }
+    Ok(())
+}
+
+fn read_input(
+    input_file: &str,
+    strategy: ReadStrategy,
+    chunk_size: usize,
+) -> std::io::Result<FileBuffer> {
+    match strategy {
+        ReadStrategy::ReadToEnd => reader::read_to_end(input_file),
+        ReadStrategy::Chunked => reader::read_chunked(input_file, chunk_size),
+        ReadStrategy::Mmap => reader::map_file(input_file),
+        ReadStrategy::Direct => reader::read_direct(input_file, chunk_size),
+    }
+}
+
+// Prints the error with the offending line of `input_file` and a caret under the column.
+fn report_parse_error(input_file: &str, err: &ParseError) {
+    println!("Error parsing {}: {}", input_file, err);
+
+    let line_start = err.position.offset - (err.position.column - 1);
+    let line = std::fs::File::open(input_file).and_then(|mut file| {
+        file.seek(SeekFrom::Start(line_start as u64))?;
+        let mut line = Vec::new();
+        BufReader::new(file).take(512).read_until(b'\n', &mut line)?;
+        Ok(line)
+    });
+    if let Ok(line) = line {
+        let line = String::from_utf8_lossy(&line);
+        println!("{}", err.snippet(line.trim_end_matches(['\r', '\n'])));
+    }
+}
+
+fn reading_error(input_file: &str, err: impl std::fmt::Display) -> String {
+    format!("Error reading {}: {}", input_file, err)
+}
+
+fn calculate_pairs(json: &JsonValue) -> Result<Vec<f64>, FromJsonError> {
+    profile_block!("Calculate pairs");
+    let pairs = {
+        profile_block!("ParseHaversinePairs");
+        Pairs::from_json(json)?
     };
 
-    let mut distances: Vec<f64> = Vec::with_capacity(pairs_array.len());
-    for pair in pairs_array {
-        let JsonValue::Object(pair_map) = pair else {
-            continue;
-        };
+    let distances = pairs
+        .as_slice()
+        .iter()
+        .map(|pair| reference_haversine(pair, 6372.8))
+        .collect();
+    Ok(distances)
+}
 
-        let (x0, y0, x1, y1) = {
-            (
-                get_number_from_json(pair_map.get("x0").unwrap()),
-                get_number_from_json(pair_map.get("y0").unwrap()),
-                get_number_from_json(pair_map.get("x1").unwrap()),
-                get_number_from_json(pair_map.get("y1").unwrap()),
-            )
-        };
+fn verify_pairwise(distances: &[f64], expected: &[f64]) {
+    profile_block!("VerifyPairwise");
+    if distances.len() != expected.len() {
+        println!(
+            "Pair Count Mismatch: calculated {}, expected {}",
+            distances.len(),
+            expected.len()
+        );
+    }
+
+    let mut mismatches = 0;
+    let mut max_difference: f64 = 0.0;
+    for (actual, expected) in distances.iter().zip(expected) {
+        let difference = (actual - expected).abs();
+        if difference > 0.0 {
+            mismatches += 1;
+        }
+        max_difference = max_difference.max(difference);
+    }
+
+    println!("Pairwise Mismatches: {}", mismatches);
+    println!("Max Pair Difference: {}", max_difference);
+}
 
-        let distance = reference_haversine(
-            &Pair::new(x0, y0, x1, y1),
-            6372.8,
+fn verify_tracks(distances: &[f64], tracks: &[TrackMetrics]) {
+    profile_block!("VerifyTracks");
+    let expected_pairs: u64 = tracks.iter().map(|track| track.pairs).sum();
+    if expected_pairs != distances.len() as u64 {
+        println!(
+            "Track Pair Count Mismatch: calculated {}, expected {}",
+            distances.len(),
+            expected_pairs
         );
-        distances.push(distance);
+        return;
     }
-    return distances;
+
+    println!("Tracks: {}", tracks.len());
+    println!("Max Track Difference: {}", max_track_difference(distances, tracks));
 }
 
-fn get_number_from_json(json: &JsonValue) -> f64 {
-    let JsonValue::Number(n) = json else {
-        return 0.0;
-    };
-    return *n;
+// Largest gap between a track's recorded distance and the sum of its pairs' distances, which
+// follow each other in `distances` in track order.
+fn max_track_difference(distances: &[f64], tracks: &[TrackMetrics]) -> f64 {
+    let mut start = 0;
+    let mut max_difference: f64 = 0.0;
+    for track in tracks {
+        let end = start + track.pairs as usize;
+        let track_distance: f64 = distances[start..end].iter().sum();
+        max_difference = max_difference.max((track_distance - track.distance).abs());
+        start = end;
+    }
+    max_difference
+}
+
+// The metrics of a generate run and the mode that regenerates its pairs.
+fn read_generation(metrics_file: &str) -> Result<(RunMetrics, PairMode), Box<dyn std::error::Error>> {
+    let metrics = read_run_metrics(metrics_file).map_err(|err| reading_error(metrics_file, err))?;
+    let mode = PairMode::from_description(&metrics.mode, &metrics.anchors)
+        .map_err(|err| reading_error(metrics_file, err))?;
+    Ok((metrics, mode))
+}
+
+// Compares the pairs array in `json` against the regenerated stream, failing on the first
+// pair that differs in any coordinate or on a length mismatch.
+fn verify_pairs(json: &JsonValue, mut stream: PairStream) -> Result<usize, Box<dyn std::error::Error>> {
+    profile_block!("VerifyPairs");
+    let pairs = Pairs::from_json(json)?;
+    let pairs = pairs.as_slice();
+
+    for (idx, pair) in pairs.iter().enumerate() {
+        let Some((_, expected)) = stream.next() else {
+            return Err(format!(
+                "Divergence at pair {}: data file has more pairs than the {} generated",
+                idx, idx
+            )
+            .into());
+        };
+
+        let fields = [
+            ("x0", pair.x0, expected.x0),
+            ("y0", pair.y0, expected.y0),
+            ("x1", pair.x1, expected.x1),
+            ("y1", pair.y1, expected.y1),
+        ];
+        for (key, actual, expected_value) in fields {
+            if actual.to_bits() != expected_value.to_bits() {
+                return Err(format!(
+                    "Divergence at pair {}: {} expected {}, found {}",
+                    idx, key, expected_value, actual
+                )
+                .into());
+            }
+        }
+    }
+
+    let missing = stream.count();
+    if missing > 0 {
+        return Err(format!(
+            "Divergence at pair {}: data file ends {} pairs early",
+            pairs.len(),
+            missing
+        )
+        .into());
+    }
+
+    Ok(pairs.len())
 }
 
 fn generate_pairs(
     file_path: &str,
     metrics_output: &str,
     distance_output: &str,
+    num_pairs: u64,
+    mode: &PairMode,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let num_pairs = 10000000;
+    match mode {
+        PairMode::Distance(distribution) => distribution.validate(6372.8)?,
+        PairMode::Trajectory(config) => config.validate(num_pairs)?,
+        PairMode::Anchors(sampling) if !sampling.jitter_km.is_finite() || sampling.jitter_km < 0.0 => {
+            return Err(format!("invalid jitter {} km", sampling.jitter_km).into());
+        }
+        _ => {}
+    }
+
     let mut cumu_distance: f64 = 0.0;
 
     let seed: u64 = rand::random();
-    let mut rng_gen = SmallRng::seed_from_u64(seed);
+    let mut stream = PairStream::new(mode, seed, num_pairs, 6372.8);
 
     let mut pairs = Pairs::with_capacity(num_pairs.try_into().unwrap());
     let mut distances: Vec<f64> = Vec::with_capacity(num_pairs.try_into().unwrap());
-    for _ in 0..num_pairs {
-        let new_pair = Pair::random_new(&mut rng_gen);
+    let mut tracks: Vec<TrackMetrics> = match mode {
+        PairMode::Trajectory(_) => vec![TrackMetrics::default(); stream.segment_count()],
+        _ => Vec::new(),
+    };
+
+    for (segment, new_pair) in &mut stream {
         pairs.push(new_pair.clone());
 
         let distance = reference_haversine(&new_pair, 6372.8);
         distances.push(distance);
         cumu_distance += distance;
+
+        if let Some(track) = tracks.get_mut(segment) {
+            track.pairs += 1;
+            track.distance += distance;
+        }
     }
 
-    let _ = pairs.save_to_file(file_path);
-    let _ = save_run_metrics(
-        &distances,
+    let metrics = RunMetrics {
         seed,
-        num_pairs,
-        cumu_distance,
-        metrics_output,
-        distance_output,
-    );
+        points: num_pairs,
+        est_distance: cumu_distance,
+        mode: mode.describe(),
+        tracks,
+        anchors: match mode {
+            PairMode::Anchors(sampling) => sampling.anchors.clone(),
+            _ => Vec::new(),
+        },
+    };
+
+    let _ = pairs.save_to_file(file_path);
+    let _ = save_run_metrics(&distances, &metrics, metrics_output, distance_output);
 
     Ok(())
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchors::NamedPoint;
+    use geodesy::Point;
+
+    // Paths for a generate run's data, metrics and distance files, unique to this process.
+    fn run_files(name: &str) -> [String; 3] {
+        ["json", "txt", "bin"].map(|extension| {
+            let file = format!("haversine-{}-{}.{}", std::process::id(), name, extension);
+            std::env::temp_dir().join(file).to_string_lossy().into_owned()
+        })
+    }
+
+    fn pairs_json(pairs: &Pairs) -> JsonValue {
+        let text = serde_json::to_string(pairs).unwrap();
+        let options = ParseOptions::default();
+        parse_file_with(text.as_bytes(), &options)
+            .and_then(|tokens| parse_tokens_with(&tokens, &options))
+            .unwrap()
+    }
+
+    fn generated(mode: &PairMode, seed: u64, num_pairs: u64) -> Pairs {
+        let mut pairs = Pairs::with_capacity(num_pairs as usize);
+        for (_, pair) in PairStream::new(mode, seed, num_pairs, 6372.8) {
+            pairs.push(pair);
+        }
+        pairs
+    }
+
+    #[test]
+    fn generated_files_verify_against_their_metrics() {
+        let anchors = ["a", "b", "c"]
+            .iter()
+            .zip([Point::new(1.5, 100.0), Point::new(-2.25, 101.5), Point::new(0.0, 99.0)])
+            .map(|(name, point)| NamedPoint { name: name.to_string(), point })
+            .collect();
+        let modes = [
+            PairMode::Uniform,
+            PairMode::Distance("log-uniform:1,500".parse().unwrap()),
+            PairMode::Trajectory(TrajectoryConfig { tracks: 7, step_km: 0.5, speed_noise: 0.2, heading_noise: 15.0 }),
+            PairMode::Anchors(AnchorSampling { source: "missing.csv".to_string(), anchors, jitter_km: 2.0 }),
+        ];
+
+        for (idx, mode) in modes.iter().enumerate() {
+            let [data, metrics_file, distances_file] = run_files(&format!("round-trip-{}", idx));
+            generate_pairs(&data, &metrics_file, &distances_file, 250, mode).unwrap();
+
+            // The anchor file named in the metrics does not exist; the recorded anchors are used.
+            let (metrics, regenerated) = read_generation(&metrics_file).unwrap();
+            assert_eq!(regenerated.describe(), mode.describe());
+
+            let json = parse_input(&data, LexerKind::Scalar, None, 0, &ParseOptions::default()).unwrap();
+            let stream = PairStream::new(&regenerated, metrics.seed, metrics.points, 6372.8);
+            assert_eq!(verify_pairs(&json, stream).unwrap(), 250, "{}", mode.describe());
+
+            let distances = calculate_pairs(&json).unwrap();
+            assert_eq!(distances, read_distances_from_file(&distances_file).unwrap());
+            if let PairMode::Trajectory(config) = mode {
+                assert_eq!(metrics.tracks.len() as u64, config.tracks);
+                assert_eq!(max_track_difference(&distances, &metrics.tracks), 0.0);
+            }
+
+            for file in [data, metrics_file, distances_file] {
+                std::fs::remove_file(file).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn tampered_pairs_are_reported_at_their_index() {
+        let mode = PairMode::Distance("uniform:10,20".parse().unwrap());
+        let original = generated(&mode, 42, 40);
+        let stream = || PairStream::new(&mode, 42, 40, 6372.8);
+        assert_eq!(verify_pairs(&pairs_json(&original), stream()).unwrap(), 40);
+
+        let mut tampered = Pairs::with_capacity(40);
+        for (idx, pair) in original.as_slice().iter().enumerate() {
+            let mut pair = pair.clone();
+            if idx == 17 {
+                pair.y1 = f64::from_bits(pair.y1.to_bits() + 1);
+            }
+            tampered.push(pair);
+        }
+        let err = verify_pairs(&pairs_json(&tampered), stream()).unwrap_err().to_string();
+        assert!(err.starts_with("Divergence at pair 17: y1 expected"), "{err}");
+
+        let short = generated(&mode, 42, 39);
+        let err = verify_pairs(&pairs_json(&short), stream()).unwrap_err().to_string();
+        assert_eq!(err, "Divergence at pair 39: data file ends 1 pairs early");
+
+        let long = generated(&mode, 42, 41);
+        let err = verify_pairs(&pairs_json(&long), stream()).unwrap_err().to_string();
+        assert_eq!(err, "Divergence at pair 40: data file has more pairs than the 40 generated");
+    }
+
+    #[test]
+    fn track_differences_point_at_the_tampered_track() {
+        let tracks = [
+            TrackMetrics { pairs: 2, distance: 3.0 },
+            TrackMetrics { pairs: 1, distance: 4.0 },
+            TrackMetrics { pairs: 3, distance: 6.0 },
+        ];
+        let mut distances = [1.0, 2.0, 4.0, 1.0, 2.0, 3.0];
+        assert_eq!(max_track_difference(&distances, &tracks), 0.0);
+        distances[4] += 0.5;
+        assert_eq!(max_track_difference(&distances, &tracks), 0.5);
+    }
+
+    #[test]
+    fn missing_files_are_named_in_the_error() {
+        let [data, metrics_file, _] = run_files("missing");
+        let err = parse_input(&data, LexerKind::Scalar, None, 0, &ParseOptions::default()).unwrap_err();
+        assert!(err.to_string().starts_with(&format!("Error reading {}: ", data)), "{err}");
+        let err = parse_input(&data, LexerKind::Span, None, 0, &ParseOptions::default()).unwrap_err();
+        assert!(err.to_string().starts_with(&format!("Error reading {}: ", data)), "{err}");
+
+        let err = read_generation(&metrics_file).unwrap_err().to_string();
+        assert!(err.starts_with(&format!("Error reading {}: ", metrics_file)), "{err}");
+
+        std::fs::write(&metrics_file, "Seed: 1\nPoints: 10\nMode: anchors 0 anchors.csv\nAnchor: 1 2 only\n").unwrap();
+        let err = read_generation(&metrics_file).unwrap_err().to_string();
+        assert_eq!(err, format!("Error reading {}: anchors.csv must contain at least two anchor points", metrics_file));
+        std::fs::remove_file(metrics_file).unwrap();
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::parser::JsonValue;
+
+// Objects up to this size are searched linearly; larger ones keep a key index.
+const INDEX_THRESHOLD: usize = 8;
+
+// A JSON object that remembers the order its keys were inserted in. Duplicate keys are only
+// stored when added with `push`; lookups then see the most recent entry.
+#[derive(Clone, Default)]
+pub struct JsonObject {
+    entries: Vec<(String, JsonValue)>,
+    // Key to the position of its latest entry, built once the object outgrows a linear scan.
+    index: Option<HashMap<String, usize>>,
+}
+
+impl JsonObject {
+    pub fn new() -> Self {
+        JsonObject::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        match &self.index {
+            Some(index) => index.get(key).copied(),
+            None => self.entries.iter().rposition(|(k, _)| k == key),
+        }
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.position(key).is_some()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        self.position(key).map(|i| &self.entries[i].1)
+    }
+
+    // Replaces the value of an existing key in place, keeping its position, or appends it.
+    pub fn insert(&mut self, key: String, value: JsonValue) -> Option<JsonValue> {
+        match self.position(&key) {
+            Some(i) => Some(std::mem::replace(&mut self.entries[i].1, value)),
+            None => {
+                self.push(key, value);
+                None
+            }
+        }
+    }
+
+    // Appends an entry even if the key is already present.
+    pub fn push(&mut self, key: String, value: JsonValue) {
+        let position = self.entries.len();
+        if let Some(index) = &mut self.index {
+            index.insert(key.clone(), position);
+        }
+        self.entries.push((key, value));
+        if self.index.is_none() && self.entries.len() > INDEX_THRESHOLD {
+            self.rebuild_index();
+        }
+    }
+
+    fn rebuild_index(&mut self) {
+        let index = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, (k, _))| (k.clone(), i))
+            .collect();
+        self.index = Some(index);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &JsonValue)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &JsonValue> {
+        self.entries.iter().map(|(_, v)| v)
+    }
+}
+
+impl IntoIterator for JsonObject {
+    type Item = (String, JsonValue);
+    type IntoIter = std::vec::IntoIter<(String, JsonValue)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+// Equal when the entries match in order; whether the key index has been built does not matter.
+impl PartialEq for JsonObject {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries == other.entries
+    }
+}
+
+impl fmt::Debug for JsonObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::parse_file_with;
+    use crate::parser::{DuplicateKeys, ParseOptions, parse_tokens_with};
+
+    fn number(n: f64) -> JsonValue {
+        JsonValue::Number(n)
+    }
+
+    fn keys(object: &JsonObject) -> Vec<&str> {
+        object.iter().map(|(k, _)| k.as_str()).collect()
+    }
+
+    #[test]
+    fn lookup_before_and_after_the_index_is_built() {
+        let mut object = JsonObject::new();
+        assert!(object.is_empty());
+        for i in 0..20 {
+            object.push(format!("k{}", i), number(i as f64));
+            assert_eq!(object.index.is_some(), i + 1 > INDEX_THRESHOLD, "{} entries", i + 1);
+            for j in 0..=i {
+                assert_eq!(object.get(&format!("k{}", j)).and_then(JsonValue::as_f64), Some(j as f64));
+            }
+            assert!(!object.contains_key(&format!("k{}", i + 1)));
+        }
+    }
+
+    #[test]
+    fn duplicates_resolve_to_the_latest_entry_on_both_sides_of_the_threshold() {
+        for size in [3, INDEX_THRESHOLD, INDEX_THRESHOLD + 1, 30] {
+            let mut object = JsonObject::new();
+            for i in 0..size {
+                object.push(format!("k{}", i), number(i as f64));
+            }
+            object.push("k0".to_string(), number(-1.0));
+            assert_eq!(object.get("k0").and_then(JsonValue::as_f64), Some(-1.0), "{size}");
+            assert_eq!(object.iter().count(), size + 1);
+
+            // `insert` replaces the latest entry in place and keeps the key order.
+            assert_eq!(object.insert("k0".to_string(), number(-2.0)).and_then(|v| v.as_f64()), Some(-1.0));
+            assert_eq!(object.get("k0").and_then(JsonValue::as_f64), Some(-2.0));
+            assert_eq!(object.values().next().and_then(JsonValue::as_f64), Some(0.0));
+            assert!(object.insert("new".to_string(), number(7.0)).is_none());
+            assert_eq!(keys(&object).last(), Some(&"new"));
+        }
+    }
+
+    fn parse(input: &str, duplicate_keys: DuplicateKeys) -> Result<JsonValue, crate::error::ParseError> {
+        let options = ParseOptions { strict: true, duplicate_keys };
+        parse_file_with(input.as_bytes(), &options).and_then(|tokens| parse_tokens_with(&tokens, &options))
+    }
+
+    #[test]
+    fn duplicate_key_policies() {
+        let small = r#"{"a": 1, "b": 2, "a": 3}"#;
+        let large = r#"{"a": 1, "b": 2, "c": 3, "d": 4, "e": 5, "f": 6, "g": 7, "h": 8, "i": 9, "a": 10, "j": 11}"#;
+        for (input, last) in [(small, 3.0), (large, 10.0)] {
+            let err = parse(input, DuplicateKeys::Error).unwrap_err();
+            assert_eq!(err.found, "duplicate key \"a\"", "{input}");
+
+            let first = parse(input, DuplicateKeys::FirstWins).unwrap();
+            assert_eq!(first["a"].as_f64(), Some(1.0));
+            assert_eq!(first.as_object().unwrap().iter().filter(|(k, _)| *k == "a").count(), 1);
+
+            let last_wins = parse(input, DuplicateKeys::LastWins).unwrap();
+            assert_eq!(last_wins["a"].as_f64(), Some(last));
+            assert_eq!(keys(last_wins.as_object().unwrap())[..2], ["a", "b"]);
+            assert_eq!(last_wins.as_object().unwrap().iter().filter(|(k, _)| *k == "a").count(), 1);
+
+            let keep_all = parse(input, DuplicateKeys::KeepAll).unwrap();
+            assert_eq!(keep_all["a"].as_f64(), Some(last));
+            let all: Vec<f64> = keep_all.as_object().unwrap().iter().filter(|(k, _)| *k == "a").filter_map(|(_, v)| v.as_f64()).collect();
+            assert_eq!(all, [1.0, last]);
+        }
+    }
+
+    #[test]
+    fn insertion_order_is_kept() {
+        let value = parse(r#"{"z": 1, "a": 2, "m": 3}"#, DuplicateKeys::LastWins).unwrap();
+        assert_eq!(keys(value.as_object().unwrap()), ["z", "a", "m"]);
+    }
+}
@@ -0,0 +1,285 @@
+use crate::events::Event;
+use crate::from_json::FromJsonError;
+use crate::generator::Pair;
+use crate::haversine::reference_haversine;
+use crate::parser::DuplicateKeys;
+
+const COORDINATES: [&str; 4] = ["x0", "y0", "x1", "y1"];
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    ExpectingRoot,
+    InRoot,
+    SkippingRootValue,
+    ExpectingPairs,
+    InPairs,
+    InPair,
+    SkippingPairValue,
+    // Waiting for the number under `COORDINATES[i]`.
+    ExpectingCoordinate(usize),
+    InCompactPair,
+    Finished,
+}
+
+// Computes each pair's distance from the events of a `{"pairs": [...]}` document as soon as
+// the pair is closed, so the document never has to be held in memory. Pairs are objects with
+// `x0`, `y0`, `x1` and `y1` in any order, or compact `[x0, y0, x1, y1]` arrays; other keys are
+// skipped.
+pub struct PairDistances {
+    earth_radius: f64,
+    duplicate_keys: DuplicateKeys,
+    state: State,
+    // Open containers inside a value being skipped.
+    skip_depth: usize,
+    seen_pairs: bool,
+    coordinates: [f64; 4],
+    // Which of `coordinates` the current pair has set, one bit each; for compact pairs, how many
+    // elements have been seen.
+    seen: usize,
+    distances: Vec<f64>,
+}
+
+impl PairDistances {
+    pub fn new(earth_radius: f64, duplicate_keys: DuplicateKeys) -> Self {
+        PairDistances {
+            earth_radius,
+            duplicate_keys,
+            state: State::ExpectingRoot,
+            skip_depth: 0,
+            seen_pairs: false,
+            coordinates: [0.0; 4],
+            seen: 0,
+            distances: Vec::new(),
+        }
+    }
+
+    // Locates an error inside the current pair.
+    fn in_pair(&self, err: FromJsonError) -> FromJsonError {
+        err.at_index(self.distances.len()).at_key("pairs")
+    }
+
+    fn push_distance(&mut self) {
+        let [x0, y0, x1, y1] = self.coordinates;
+        let distance = reference_haversine(&Pair::new(x0, y0, x1, y1), self.earth_radius);
+        self.distances.push(distance);
+        self.state = State::InPairs;
+    }
+
+    pub fn push(&mut self, event: Event<'_>) -> Result<(), FromJsonError> {
+        if self.skip_depth > 0 {
+            match event {
+                Event::StartObject | Event::StartArray => self.skip_depth += 1,
+                Event::EndObject | Event::EndArray => self.skip_depth -= 1,
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        self.state = match (self.state, event) {
+            (State::ExpectingRoot, Event::StartObject) => State::InRoot,
+            (State::ExpectingRoot, event) => {
+                return Err(FromJsonError::new(format!("expected an object, found {}", event.describe())));
+            }
+            (State::InRoot, Event::Key(key)) if key == "pairs" => {
+                if !self.seen_pairs {
+                    self.seen_pairs = true;
+                    State::ExpectingPairs
+                } else {
+                    // A repeated `pairs` resolves as it would in the document tree.
+                    match self.duplicate_keys {
+                        DuplicateKeys::Error => {
+                            return Err(FromJsonError::new("duplicate key").at_key("pairs"));
+                        }
+                        DuplicateKeys::FirstWins => State::SkippingRootValue,
+                        DuplicateKeys::LastWins | DuplicateKeys::KeepAll => {
+                            self.distances.clear();
+                            State::ExpectingPairs
+                        }
+                    }
+                }
+            }
+            (State::InRoot, Event::Key(_)) => State::SkippingRootValue,
+            (State::InRoot, _) => {
+                if !self.seen_pairs {
+                    return Err(FromJsonError::new("missing field").at_key("pairs"));
+                }
+                State::Finished
+            }
+            (State::SkippingRootValue | State::SkippingPairValue, event) => {
+                if matches!(event, Event::StartObject | Event::StartArray) {
+                    self.skip_depth = 1;
+                }
+                match self.state {
+                    State::SkippingRootValue => State::InRoot,
+                    _ => State::InPair,
+                }
+            }
+            (State::ExpectingPairs, Event::StartArray) => State::InPairs,
+            (State::ExpectingPairs, event) => {
+                return Err(FromJsonError::new(format!("expected an array, found {}", event.describe()))
+                    .at_key("pairs"));
+            }
+            (State::InPairs, Event::StartObject) => {
+                self.seen = 0;
+                State::InPair
+            }
+            (State::InPairs, Event::StartArray) => {
+                self.seen = 0;
+                State::InCompactPair
+            }
+            (State::InPairs, Event::EndArray) => State::InRoot,
+            (State::InPairs, event) => {
+                return Err(self.in_pair(FromJsonError::new(format!(
+                    "expected an object or an array, found {}",
+                    event.describe()
+                ))));
+            }
+            (State::InPair, Event::Key(key)) => match COORDINATES.iter().position(|&name| key == name) {
+                Some(i) => {
+                    if self.seen & (1 << i) != 0 && self.duplicate_keys == DuplicateKeys::Error {
+                        return Err(self.in_pair(FromJsonError::new("duplicate key").at_key(COORDINATES[i])));
+                    }
+                    State::ExpectingCoordinate(i)
+                }
+                None => State::SkippingPairValue,
+            },
+            (State::InPair, _) => {
+                if let Some(missing) = (0..4).find(|i| self.seen & (1 << i) == 0) {
+                    return Err(self.in_pair(FromJsonError::new("missing field").at_key(COORDINATES[missing])));
+                }
+                self.push_distance();
+                return Ok(());
+            }
+            (State::ExpectingCoordinate(i), Event::Number(n)) => {
+                let first = self.seen & (1 << i) == 0;
+                if first || self.duplicate_keys != DuplicateKeys::FirstWins {
+                    self.coordinates[i] = n;
+                }
+                self.seen |= 1 << i;
+                State::InPair
+            }
+            (State::ExpectingCoordinate(i), event) => {
+                let err = FromJsonError::new(format!("expected a number, found {}", event.describe()));
+                return Err(self.in_pair(err.at_key(COORDINATES[i])));
+            }
+            (State::InCompactPair, Event::Number(n)) => {
+                if self.seen < 4 {
+                    self.coordinates[self.seen] = n;
+                }
+                self.seen += 1;
+                State::InCompactPair
+            }
+            (State::InCompactPair, Event::EndArray) => {
+                if self.seen != 4 {
+                    return Err(self.in_pair(FromJsonError::new(format!(
+                        "expected an array of 4 elements, found {}",
+                        self.seen
+                    ))));
+                }
+                self.push_distance();
+                return Ok(());
+            }
+            (State::InCompactPair, event) => {
+                let err = FromJsonError::new(format!("expected a number, found {}", event.describe()));
+                return Err(self.in_pair(err.at_index(self.seen)));
+            }
+            (State::Finished, _) => State::Finished,
+        };
+        Ok(())
+    }
+
+    // The distances in document order, once the root object has closed.
+    pub fn finish(self) -> Result<Vec<f64>, FromJsonError> {
+        match self.state {
+            State::Finished => Ok(self.distances),
+            _ => Err(FromJsonError::new("document ended early")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::parse_events;
+    use crate::lexer::TokenStream;
+    use crate::parser::ParseOptions;
+
+    const EARTH_RADIUS: f64 = 6372.8;
+
+    fn distances(input: &str, duplicate_keys: DuplicateKeys) -> Result<Vec<f64>, String> {
+        let options = ParseOptions { strict: true, duplicate_keys };
+        let mut pairs = PairDistances::new(EARTH_RADIUS, duplicate_keys);
+        for event in parse_events(TokenStream::new(input.as_bytes(), &options), &options) {
+            pairs.push(event.map_err(|err| err.to_string())?).map_err(|err| err.to_string())?;
+        }
+        pairs.finish().map_err(|err| err.to_string())
+    }
+
+    fn haversine(x0: f64, y0: f64, x1: f64, y1: f64) -> f64 {
+        reference_haversine(&Pair::new(x0, y0, x1, y1), EARTH_RADIUS)
+    }
+
+    #[test]
+    fn keys_in_any_order_and_compact_pairs() {
+        let input = r#"{"pairs": [
+            {"x0": 1, "y0": 2, "x1": 3, "y1": 4},
+            {"y1": 4, "x1": 3, "y0": 2, "x0": 1},
+            [1, 2, 3, 4],
+            {"x1": 10, "x0": -5, "y1": 20, "y0": 0}
+        ]}"#;
+        let expected = haversine(1.0, 2.0, 3.0, 4.0);
+        assert_eq!(
+            distances(input, DuplicateKeys::LastWins).unwrap(),
+            [expected, expected, expected, haversine(-5.0, 0.0, 10.0, 20.0)]
+        );
+        assert_eq!(distances(r#"{"pairs": []}"#, DuplicateKeys::LastWins).unwrap(), Vec::<f64>::new());
+    }
+
+    #[test]
+    fn unknown_keys_and_nested_values_are_skipped() {
+        let input = r#"{
+            "meta": {"pairs": [[9, 9, 9, 9]], "nested": [{"x0": 7}]},
+            "pairs": [{"id": {"x0": [1, {"y0": 2}]}, "x0": 1, "tags": [[], {}], "y0": 2, "x1": 3, "y1": 4}],
+            "after": [[1, 2, 3, 4]]
+        }"#;
+        assert_eq!(distances(input, DuplicateKeys::LastWins).unwrap(), [haversine(1.0, 2.0, 3.0, 4.0)]);
+    }
+
+    #[test]
+    fn missing_and_mistyped_fields() {
+        for (input, message) in [
+            (r#"{"pairs": [{"x0": 1, "y0": 2, "x1": 3}]}"#, "$.pairs[0].y1: missing field"),
+            (r#"{"pairs": [[1, 2, 3, 4], {"x0": 1, "y0": "2", "x1": 3, "y1": 4}]}"#, "$.pairs[1].y0: expected a number, found a string"),
+            (r#"{"pairs": [[1, 2, 3]]}"#, "$.pairs[0]: expected an array of 4 elements, found 3"),
+            (r#"{"pairs": [[1, 2, null, 4]]}"#, "$.pairs[0][2]: expected a number, found null"),
+            (r#"{"pairs": [1]}"#, "$.pairs[0]: expected an object or an array, found a number"),
+            (r#"{"pairs": {}}"#, "$.pairs: expected an array, found an object"),
+            (r#"{"other": []}"#, "$.pairs: missing field"),
+            ("[]", "$: expected an object, found an array"),
+        ] {
+            assert_eq!(distances(input, DuplicateKeys::LastWins).unwrap_err(), message, "{input}");
+        }
+    }
+
+    #[test]
+    fn duplicate_coordinates_follow_the_policy() {
+        let input = r#"{"pairs": [{"x0": 1, "y0": 2, "x1": 3, "y1": 4, "x0": 5}]}"#;
+        assert_eq!(distances(input, DuplicateKeys::Error).unwrap_err(), "$.pairs[0].x0: duplicate key");
+        assert_eq!(distances(input, DuplicateKeys::FirstWins).unwrap(), [haversine(1.0, 2.0, 3.0, 4.0)]);
+        assert_eq!(distances(input, DuplicateKeys::LastWins).unwrap(), [haversine(5.0, 2.0, 3.0, 4.0)]);
+        assert_eq!(distances(input, DuplicateKeys::KeepAll).unwrap(), [haversine(5.0, 2.0, 3.0, 4.0)]);
+    }
+
+    #[test]
+    fn duplicate_pairs_follow_the_policy() {
+        let input = r#"{"pairs": [[1, 2, 3, 4], [0, 0, 1, 1]], "x": 1, "pairs": [[5, 6, 7, 8]]}"#;
+        assert_eq!(distances(input, DuplicateKeys::Error).unwrap_err(), "$.pairs: duplicate key");
+        assert_eq!(
+            distances(input, DuplicateKeys::FirstWins).unwrap(),
+            [haversine(1.0, 2.0, 3.0, 4.0), haversine(0.0, 0.0, 1.0, 1.0)]
+        );
+        assert_eq!(distances(input, DuplicateKeys::LastWins).unwrap(), [haversine(5.0, 6.0, 7.0, 8.0)]);
+        assert_eq!(distances(input, DuplicateKeys::KeepAll).unwrap(), [haversine(5.0, 6.0, 7.0, 8.0)]);
+        assert_eq!(distances(r#"{"pairs": [[1, 2, 3, 4]], "pairs": []}"#, DuplicateKeys::LastWins).unwrap(), Vec::<f64>::new());
+    }
+}
@@ -0,0 +1,254 @@
+use crate::error::{ParseError, Position};
+use crate::float::parse_f64;
+use crate::generator::Pair;
+use crate::haversine::reference_haversine;
+use crate::lexer::{describe_byte, is_rfc8259_number};
+use crate::{profile_bandwidth, profile_block};
+
+const COORDINATE_KEYS: &str = "\"x0\", \"y0\", \"x1\" or \"y1\"";
+
+// Every pair's coordinates, one column per field, in file order.
+#[derive(Debug, Default)]
+pub struct PairColumns {
+    pub x0: Vec<f64>,
+    pub y0: Vec<f64>,
+    pub x1: Vec<f64>,
+    pub y1: Vec<f64>,
+}
+
+impl PairColumns {
+    pub fn len(&self) -> usize {
+        self.x0.len()
+    }
+
+    pub fn distances(&self, earth_radius: f64) -> Vec<f64> {
+        profile_block!("Calculate pairs");
+        (0..self.len())
+            .map(|i| {
+                let pair = Pair::new(self.x0[i], self.y0[i], self.x1[i], self.y1[i]);
+                reference_haversine(&pair, earth_radius)
+            })
+            .collect()
+    }
+
+    fn column(&mut self, field: usize) -> &mut Vec<f64> {
+        match field {
+            0 => &mut self.x0,
+            1 => &mut self.y0,
+            2 => &mut self.x1,
+            _ => &mut self.y1,
+        }
+    }
+}
+
+struct Cursor<'a> {
+    buffer: &'a [u8],
+    offset: usize,
+}
+
+impl Cursor<'_> {
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.buffer.get(self.offset) {
+            self.offset += 1;
+        }
+    }
+
+    // Skips whitespace and returns the next byte without consuming it.
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.buffer.get(self.offset).copied()
+    }
+
+    fn mismatch(&self, expected: &str) -> ParseError {
+        let found = self
+            .buffer
+            .get(self.offset)
+            .map_or("end of input".to_string(), |&byte| describe_byte(byte));
+        ParseError::new(Position::locate(self.buffer, self.offset), found, expected)
+    }
+
+    fn expect(&mut self, byte: u8, expected: &str) -> Result<(), ParseError> {
+        if self.peek() != Some(byte) {
+            return Err(self.mismatch(expected));
+        }
+        self.offset += 1;
+        Ok(())
+    }
+
+    fn expect_literal(&mut self, literal: &[u8], expected: &str) -> Result<(), ParseError> {
+        self.skip_whitespace();
+        if !self.buffer[self.offset..].starts_with(literal) {
+            return Err(self.mismatch(expected));
+        }
+        self.offset += literal.len();
+        Ok(())
+    }
+
+    // One of the four coordinate keys, as its column number.
+    fn coordinate_key(&mut self) -> Result<usize, ParseError> {
+        self.skip_whitespace();
+        let field = match self.buffer.get(self.offset..self.offset + 4) {
+            Some(b"\"x0\"") => 0,
+            Some(b"\"y0\"") => 1,
+            Some(b"\"x1\"") => 2,
+            Some(b"\"y1\"") => 3,
+            _ => return Err(self.mismatch(COORDINATE_KEYS)),
+        };
+        self.offset += 4;
+        Ok(field)
+    }
+
+    // An RFC 8259 number, checked with the lexers' grammar so `parse_f64` only sees valid input.
+    fn number(&mut self) -> Result<f64, ParseError> {
+        self.skip_whitespace();
+        let start = self.offset;
+        while let Some(b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E') = self.buffer.get(self.offset) {
+            self.offset += 1;
+        }
+        if self.offset == start {
+            return Err(self.mismatch("a number"));
+        }
+        // Only ASCII bytes were taken, so this is always valid UTF-8.
+        let text = std::str::from_utf8(&self.buffer[start..self.offset]).unwrap();
+        if !is_rfc8259_number(text) {
+            return Err(ParseError::new(
+                Position::locate(self.buffer, start),
+                format!("'{}'", text),
+                "an RFC 8259 number",
+            ));
+        }
+        parse_f64(text.as_bytes()).ok_or_else(|| self.mismatch("a number"))
+    }
+
+    // `{"x0":..,"y0":..,"x1":..,"y1":..}` with the keys in any order, each exactly once.
+    fn pair(&mut self, columns: &mut PairColumns) -> Result<(), ParseError> {
+        self.expect(b'{', "'{' starting a pair")?;
+        let mut seen = 0u8;
+        for i in 0..4 {
+            if i > 0 {
+                self.expect(b',', "','")?;
+            }
+            let key_start = self.offset;
+            let field = self.coordinate_key()?;
+            if seen & (1 << field) != 0 {
+                self.offset = key_start;
+                self.skip_whitespace();
+                return Err(self.mismatch("a coordinate key not already in the pair"));
+            }
+            seen |= 1 << field;
+            self.expect(b':', "':'")?;
+            let value = self.number()?;
+            columns.column(field).push(value);
+        }
+        self.expect(b'}', "'}' after the fourth coordinate")
+    }
+}
+
+// Parses exactly `{"pairs":[{"x0":..,"y0":..,"x1":..,"y1":..},...]}` straight into columns,
+// skipping tokens and the document tree. Anything else, including extra keys, compact pairs or
+// duplicate keys, is rejected with the position where the layout stopped matching so the caller
+// can fall back to the generic parser.
+pub fn parse_pair_columns(buffer: &[u8]) -> Result<PairColumns, ParseError> {
+    profile_bandwidth!("Parse pairs schema", buffer.len());
+    let mut cursor = Cursor { buffer, offset: 0 };
+    let mut columns = PairColumns::default();
+
+    cursor.expect(b'{', "'{'")?;
+    cursor.expect_literal(b"\"pairs\"", "\"pairs\" as the only key")?;
+    cursor.expect(b':', "':'")?;
+    cursor.expect(b'[', "'['")?;
+    if cursor.peek() == Some(b']') {
+        cursor.offset += 1;
+    } else {
+        loop {
+            cursor.pair(&mut columns)?;
+            match cursor.peek() {
+                Some(b',') => cursor.offset += 1,
+                Some(b']') => {
+                    cursor.offset += 1;
+                    break;
+                }
+                _ => return Err(cursor.mismatch("',' or ']'")),
+            }
+        }
+    }
+    cursor.expect(b'}', "'}' after the pairs array")?;
+    if cursor.peek().is_some() {
+        return Err(cursor.mismatch("end of input"));
+    }
+    Ok(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_json::FromJson;
+    use crate::generator::Pairs;
+    use crate::lexer::parse_file_with;
+    use crate::parser::{ParseOptions, parse_tokens_with};
+
+    // The generic path: the whole document tree, then `Pairs::from_json`.
+    fn generic_columns(input: &str) -> PairColumns {
+        let options = ParseOptions::default();
+        let json = parse_tokens_with(&parse_file_with(input.as_bytes(), &options).unwrap(), &options).unwrap();
+        let pairs = Pairs::from_json(&json).unwrap();
+        let mut columns = PairColumns::default();
+        for pair in pairs.as_slice() {
+            columns.x0.push(pair.x0);
+            columns.y0.push(pair.y0);
+            columns.x1.push(pair.x1);
+            columns.y1.push(pair.y1);
+        }
+        columns
+    }
+
+    fn assert_matches_generic(input: &str) {
+        let columns = parse_pair_columns(input.as_bytes()).unwrap();
+        let expected = generic_columns(input);
+        assert_eq!((&columns.x0, &columns.y0, &columns.x1, &columns.y1), (&expected.x0, &expected.y0, &expected.x1, &expected.y1));
+        assert_eq!(columns.distances(6372.8).len(), columns.len());
+    }
+
+    #[test]
+    fn keys_in_any_order() {
+        assert_matches_generic(
+            r#"{"pairs":[{"x0":1.5,"y0":-2,"x1":3e2,"y1":0.25},{"y1":4,"x1":3,"y0":2,"x0":1},{"x1":-0.0,"y0":1E-3,"y1":12345678901234567890,"x0":0}]}"#,
+        );
+        assert_matches_generic(" {\n \"pairs\" : [ { \"y0\" : 1 , \"x0\" : 2 , \"y1\" : 3 , \"x1\" : 4 } ] } \n");
+    }
+
+    #[test]
+    fn empty_pairs() {
+        assert_eq!(parse_pair_columns(br#"{"pairs":[]}"#).unwrap().len(), 0);
+        assert_eq!(parse_pair_columns(b" { \"pairs\" : [ ] } ").unwrap().len(), 0);
+    }
+
+    // Each of these is valid for the generic parser but outside the schema, so the caller falls back.
+    #[test]
+    fn other_layouts_are_rejected() {
+        for (input, found, expected) in [
+            (r#"{"pairs":[{"x0":1,"y0":2,"x1":3,"y1":4,"id":5}]}"#, "','", "'}' after the fourth coordinate"),
+            (r#"{"pairs":[{"x0":1,"id":5,"y0":2,"x1":3,"y1":4}]}"#, "'\"'", "\"x0\", \"y0\", \"x1\" or \"y1\""),
+            (r#"{"pairs":[[1,2,3,4]]}"#, "'['", "'{' starting a pair"),
+            (r#"{"pairs":[{"x0":1,"y0":2,"x0":3,"y1":4}]}"#, "'\"'", "a coordinate key not already in the pair"),
+            (r#"{"meta":1,"pairs":[]}"#, "'\"'", "\"pairs\" as the only key"),
+            (r#"{"pairs":[],"meta":1}"#, "','", "'}' after the pairs array"),
+        ] {
+            let err = parse_pair_columns(input.as_bytes()).unwrap_err();
+            assert_eq!((err.found.as_str(), err.expected.as_str()), (found, expected), "{input}");
+            // The generic path still reads it.
+            let options = ParseOptions::default();
+            assert!(parse_file_with(input.as_bytes(), &options).and_then(|tokens| parse_tokens_with(&tokens, &options)).is_ok());
+        }
+    }
+
+    #[test]
+    fn numbers_follow_rfc_8259() {
+        for number in ["01", "1.", ".5", "-", "+1", "1e", "1e+", "--1", "1.2.3", "0x10"] {
+            let input = format!(r#"{{"pairs":[{{"x0":{},"y0":2,"x1":3,"y1":4}}]}}"#, number);
+            assert!(parse_pair_columns(input.as_bytes()).is_err(), "{number}");
+        }
+        let err = parse_pair_columns(br#"{"pairs":[{"x0":01,"y0":2,"x1":3,"y1":4}]}"#).unwrap_err();
+        assert_eq!((err.position.offset, err.found.as_str(), err.expected.as_str()), (16, "'01'", "an RFC 8259 number"));
+    }
+}
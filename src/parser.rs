@@ -1,191 +1,403 @@
-use crate::lexer::Token;
+use crate::error::{ParseError, Position};
+use crate::events::{Event, EventParser};
+use crate::lexer::{Lexeme, TokenRef};
 use crate::profile_block;
-use std::collections::HashMap;
+use crate::object::JsonObject;
+use std::fmt;
+use std::ops::Index;
+use std::str::FromStr;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub enum StateItem {
     InObject,
     InArray,
     ExpectingKey,
+    ExpectingKeyOrEndObject,
     ExpectingColon,
     ExpectingValue,
     ExpectingValueInObject,
     ExpectingValueInArray,
+    ExpectingValueOrEndArray,
     ExpectingCommaOrEndObject,
     ExpectingCommaOrEndArray,
 }
 
-#[derive(Debug, Clone)]
+impl StateItem {
+    // What the grammar accepts next in this state, for error messages.
+    pub fn expected(&self) -> &'static str {
+        match self {
+            StateItem::InObject => "'}'",
+            StateItem::InArray => "']'",
+            StateItem::ExpectingKey => "a string key",
+            StateItem::ExpectingKeyOrEndObject => "a string key or '}'",
+            StateItem::ExpectingColon => "':'",
+            StateItem::ExpectingValue
+            | StateItem::ExpectingValueInObject
+            | StateItem::ExpectingValueInArray => "a value",
+            StateItem::ExpectingValueOrEndArray => "a value or ']'",
+            StateItem::ExpectingCommaOrEndObject => "',' or '}'",
+            StateItem::ExpectingCommaOrEndArray => "',' or ']'",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum JsonValue {
     String(String),
     Number(f64),
-    Object(HashMap<String, JsonValue>),
+    Bool(bool),
+    Null,
+    Object(JsonObject),
     Array(Vec<JsonValue>),
 }
 
-pub fn parse_tokens(tokens: &[Token]) -> Option<JsonValue> {
-    profile_block!("Parse tokens");
-    let mut state_stack: Vec<StateItem> = Vec::new();
-    let mut parser_stack: Vec<JsonValue> = Vec::new();
-    let mut key_stack: Vec<String> = Vec::new();
-
-    state_stack.push(StateItem::ExpectingValue);
-
-    for token in tokens {
-        let current_state = state_stack.last().unwrap();
-        match (current_state, &token) {
-            (
-                StateItem::ExpectingValue
-                | StateItem::ExpectingValueInObject
-                | StateItem::ExpectingValueInArray,
-                Token::OpenBrace,
-            ) => {
-                state_stack.pop();
-                state_stack.push(StateItem::InObject);
-                state_stack.push(StateItem::ExpectingKey);
-
-                parser_stack.push(JsonValue::Object(HashMap::new()));
-            }
-            (StateItem::ExpectingKey, Token::StringContent(s)) => {
-                state_stack.pop();
-                state_stack.push(StateItem::ExpectingColon);
-                key_stack.push(s.clone());
-            }
-            (StateItem::ExpectingColon, Token::Colon) => {
-                state_stack.pop();
-                state_stack.push(StateItem::ExpectingCommaOrEndObject);
-                state_stack.push(StateItem::ExpectingValueInObject);
-            }
-            (
-                StateItem::ExpectingValueInObject | StateItem::ExpectingValueInArray,
-                Token::StringContent(s),
-            ) => {
-                state_stack.pop();
-                if let Some(key) = key_stack.pop() {
-                    if let Some(JsonValue::Object(map)) = parser_stack.last_mut() {
-                        let value = JsonValue::String(s.clone());
-                        map.insert(key, value);
-                    }
-                }
-            }
-            (StateItem::ExpectingValueInObject, Token::Number(n)) => {
-                state_stack.pop();
-                if let Some(key) = key_stack.pop() {
-                    if let Some(JsonValue::Object(map)) = parser_stack.last_mut() {
-                        let value = JsonValue::Number(n.clone());
-                        map.insert(key, value);
-                    }
-                }
-            }
-            (StateItem::ExpectingCommaOrEndObject, Token::Comma) => {
-                state_stack.pop();
-                state_stack.push(StateItem::ExpectingKey);
-            }
-            (StateItem::ExpectingCommaOrEndObject, Token::CloseBrace) => {
-                state_stack.pop(); // ExpectingCommaOrEndObject
-                state_stack.pop(); // InObject
-
-                if let Some(finished_obj) = parser_stack.pop() {
-                    // Check the parent container (the new top of the stack) to decide what to do.
-                    if let Some(parent) = parser_stack.last_mut() {
-                        // Peek at parent
-                        match parent {
-                            JsonValue::Array(arr) => {
-                                arr.push(finished_obj);
-                            }
-                            JsonValue::Object(map) => {
-                                if let Some(key) = key_stack.pop() {
-                                    map.insert(key, finished_obj);
-                                }
-                            }
-                            _ => {
-                                panic!("Error: Expected array or object");
-                            }
-                        }
-                    } else {
-                        parser_stack.push(finished_obj); // Is root object
-                    }
+// Returned by indexing when the key or position is absent, so lookups can be chained.
+static NULL: JsonValue = JsonValue::Null;
+
+impl JsonValue {
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&JsonObject> {
+        match self {
+            JsonValue::Object(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, JsonValue::Null)
+    }
+
+    // The value under `key` if this is an object holding it.
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        self.as_object()?.get(key)
+    }
+
+    // The element at `index` if this is an array that long.
+    pub fn get_index(&self, index: usize) -> Option<&JsonValue> {
+        self.as_array()?.get(index)
+    }
+}
+
+impl Index<&str> for JsonValue {
+    type Output = JsonValue;
+
+    fn index(&self, key: &str) -> &JsonValue {
+        self.get(key).unwrap_or(&NULL)
+    }
+}
+
+impl Index<usize> for JsonValue {
+    type Output = JsonValue;
+
+    fn index(&self, index: usize) -> &JsonValue {
+        self.get_index(index).unwrap_or(&NULL)
+    }
+}
+
+// What to do when an object repeats a key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    Error,
+    FirstWins,
+    #[default]
+    LastWins,
+    KeepAll,
+}
+
+impl FromStr for DuplicateKeys {
+    type Err = String;
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy {
+            "error" => Ok(DuplicateKeys::Error),
+            "first" => Ok(DuplicateKeys::FirstWins),
+            "last" => Ok(DuplicateKeys::LastWins),
+            "keep-all" => Ok(DuplicateKeys::KeepAll),
+            _ => Err(format!("unknown duplicate key policy '{}' (expected error, first, last or keep-all)", policy)),
+        }
+    }
+}
+
+impl fmt::Display for DuplicateKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let policy = match self {
+            DuplicateKeys::Error => "error",
+            DuplicateKeys::FirstWins => "first",
+            DuplicateKeys::LastWins => "last",
+            DuplicateKeys::KeepAll => "keep-all",
+        };
+        write!(f, "{}", policy)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    // Reject anything RFC 8259 does not allow instead of skipping over it.
+    pub strict: bool,
+    pub duplicate_keys: DuplicateKeys,
+}
+
+// Push-driven parsing state machine: tokens are fed in one at a time, from whichever tokenizer
+// produced them, and the finished value is collected with `finish`.
+pub struct Parser {
+    duplicate_keys: DuplicateKeys,
+    events: EventParser,
+    parser_stack: Vec<JsonValue>,
+    key_stack: Vec<String>,
+}
+
+impl Parser {
+    pub fn new(options: &ParseOptions) -> Self {
+        Parser {
+            duplicate_keys: options.duplicate_keys,
+            events: EventParser::new(options),
+            parser_stack: Vec::new(),
+            key_stack: Vec::new(),
+        }
+    }
+
+    // `position` is only evaluated when the token has to be reported in an error.
+    pub fn push_token(
+        &mut self,
+        token: TokenRef<'_>,
+        position: impl Fn() -> Position,
+    ) -> Result<(), ParseError> {
+        let Some(event) = self.events.push_token(token, &position)? else {
+            return Ok(());
+        };
+        match event {
+            Event::StartObject => self.parser_stack.push(JsonValue::Object(JsonObject::new())),
+            Event::StartArray => self.parser_stack.push(JsonValue::Array(Vec::new())),
+            Event::Key(key) => {
+                if self.duplicate_keys == DuplicateKeys::Error
+                    && let Some(JsonValue::Object(map)) = self.parser_stack.last()
+                    && map.contains_key(&key)
+                {
+                    return Err(ParseError::new(
+                        position(),
+                        format!("duplicate key \"{}\"", key),
+                        "a key not already in the object",
+                    ));
                 }
+                self.key_stack.push(key.into_owned());
             }
-            (StateItem::ExpectingValue | StateItem::ExpectingValueInObject, Token::OpenBracket) => {
-                state_stack.pop();
-                state_stack.push(StateItem::InArray);
-                state_stack.push(StateItem::ExpectingCommaOrEndArray);
-                state_stack.push(StateItem::ExpectingValueInArray);
-                parser_stack.push(JsonValue::Array(Vec::new()));
-            }
-            (StateItem::ExpectingCommaOrEndArray, Token::Comma) => {
-                state_stack.pop();
-                state_stack.push(StateItem::ExpectingCommaOrEndArray);
-                state_stack.push(StateItem::ExpectingValueInArray);
-            }
-            (StateItem::ExpectingCommaOrEndArray, Token::CloseBracket) => {
-                state_stack.pop(); // ExpectingCommaOrEndArray
-                state_stack.pop(); // InArray
-
-                if let Some(finished_arr) = parser_stack.pop() {
-                    if let Some(parent) = parser_stack.last_mut() {
-                        // Peek at parent
-                        match parent {
-                            JsonValue::Array(arr) => {
-                                arr.push(finished_arr);
-                            }
-                            JsonValue::Object(map) => {
-                                if let Some(key) = key_stack.pop() {
-                                    map.insert(key, finished_arr);
-                                }
-                            }
-                            _ => {
-                                panic!("Error: Expected array or object");
-                            }
+            Event::EndObject | Event::EndArray => self.close_container(),
+            Event::String(s) => self.add_value(JsonValue::String(s.into_owned())),
+            Event::Number(n) => self.add_value(JsonValue::Number(n)),
+            Event::Bool(b) => self.add_value(JsonValue::Bool(b)),
+            Event::Null => self.add_value(JsonValue::Null),
+        }
+        Ok(())
+    }
+
+    // Stores a completed value in the container on top of `parser_stack`, under the pending key
+    // if that container is an object, or leaves it as the root.
+    fn add_value(&mut self, value: JsonValue) {
+        match self.parser_stack.last_mut() {
+            Some(JsonValue::Array(arr)) => arr.push(value),
+            Some(JsonValue::Object(map)) => {
+                if let Some(key) = self.key_stack.pop() {
+                    match self.duplicate_keys {
+                        DuplicateKeys::FirstWins if map.contains_key(&key) => {}
+                        DuplicateKeys::KeepAll => map.push(key, value),
+                        _ => {
+                            map.insert(key, value);
                         }
-                    } else {
-                        parser_stack.push(finished_arr); // is root
                     }
                 }
             }
-            _ => continue,
+            _ => self.parser_stack.push(value), // Is root
+        }
+    }
+
+    // Moves a finished object or array from the top of `parser_stack` into its parent.
+    fn close_container(&mut self) {
+        if let Some(finished) = self.parser_stack.pop() {
+            self.add_value(finished);
         }
     }
 
-    assert!(state_stack.len() == 0);
-    assert!(parser_stack.len() == 1);
+    // Hands out the root value once it is complete and resets the parser for the next one.
+    pub fn take_document(&mut self) -> Option<JsonValue> {
+        if !self.events.is_complete() {
+            return None;
+        }
+        let document = self.parser_stack.pop()?;
+        self.events.start_next_document();
+        Some(document)
+    }
 
-    return parser_stack.last().cloned();
-}
+    // True when no part of a value has been seen since the start or the last `take_document`.
+    pub fn is_between_documents(&self) -> bool {
+        self.parser_stack.is_empty() && self.events.is_between_documents()
+    }
 
-pub fn format_json(json: &JsonValue) -> String {
-    let mut output = String::new();
-    match json {
-        JsonValue::String(s) => {
-            output = format!("\"{}\"", s);
-            output
+    // `end` locates the end of the input for the error raised when the value is incomplete.
+    pub fn finish(mut self, end: impl FnOnce() -> Position) -> Result<JsonValue, ParseError> {
+        if !self.events.is_complete() || self.parser_stack.len() != 1 {
+            return Err(ParseError::new(end(), "end of input", self.expected()));
         }
-        JsonValue::Number(n) => {
-            output = n.to_string();
-            output
+
+        Ok(self.parser_stack.pop().unwrap())
+    }
+
+    // What the parser needs next to make progress, for error messages.
+    pub fn expected(&self) -> &'static str {
+        self.events.expected()
+    }
+}
+
+pub fn parse_tokens(tokens: &[Lexeme]) -> Result<JsonValue, ParseError> {
+    parse_tokens_with(tokens, &ParseOptions::default())
+}
+
+pub fn parse_tokens_with(tokens: &[Lexeme], options: &ParseOptions) -> Result<JsonValue, ParseError> {
+    profile_block!("Parse tokens");
+    let mut parser = Parser::new(options);
+
+    for lexeme in tokens {
+        parser.push_token(lexeme.token.to_ref(), || lexeme.position)?;
+    }
+
+    parser.finish(|| tokens.last().map_or(Position::start(), |lexeme| lexeme.position))
+}
+
+// Single-pass alternative to `parse_tokens`: pulls tokens as the lexer produces them, so the
+// intermediate token buffer is never allocated. The returned tree is still built in full.
+pub fn parse_stream<I>(tokens: I, options: &ParseOptions) -> Result<JsonValue, ParseError>
+where
+    I: IntoIterator<Item = Result<Lexeme, ParseError>>,
+{
+    profile_block!("Parse stream");
+    let mut parser = Parser::new(options);
+    let mut last_position = Position::start();
+
+    for lexeme in tokens {
+        let Lexeme { token, position } = lexeme?;
+        last_position = position;
+        parser.push_token(token.into_ref(), || position)?;
+    }
+
+    parser.finish(|| last_position)
+}
+
+// Parses concatenated or whitespace-separated JSON values, yielding each one as soon as its last
+// token arrives.
+pub struct Documents<I> {
+    tokens: I,
+    parser: Parser,
+    last_position: Position,
+    done: bool,
+}
+
+impl<I> Iterator for Documents<I>
+where
+    I: Iterator<Item = Result<Lexeme, ParseError>>,
+{
+    type Item = Result<JsonValue, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
         }
-        JsonValue::Object(map) => {
-            output.push('{');
-            for (i, (key, value)) in map.iter().enumerate() {
-                if i != 0 {
-                    output.push(',');
+        let result = loop {
+            let Some(lexeme) = self.tokens.next() else {
+                if self.parser.is_between_documents() {
+                    self.done = true;
+                    return None;
                 }
-                output.push_str(&format!("\"{}\":{}", key, format_json(value)));
+                break Err(ParseError::new(self.last_position, "end of input", self.parser.expected()));
+            };
+            let pushed = lexeme.and_then(|Lexeme { token, position }| {
+                self.last_position = position;
+                self.parser.push_token(token.into_ref(), || position)
+            });
+            if let Err(err) = pushed {
+                break Err(err);
             }
-            output.push('}');
-            output
-        }
-        JsonValue::Array(arr) => {
-            output.push('[');
-            for (i, value) in arr.iter().enumerate() {
-                if i != 0 {
-                    output.push(',');
-                }
-                output.push_str(&format_json(value));
+            if let Some(document) = self.parser.take_document() {
+                break Ok(document);
+            }
+        };
+        // The parser cannot resynchronise after an error, so stop there.
+        self.done = result.is_err();
+        Some(result)
+    }
+}
+
+pub fn parse_documents<I>(tokens: I, options: &ParseOptions) -> Documents<I::IntoIter>
+where
+    I: IntoIterator<Item = Result<Lexeme, ParseError>>,
+{
+    Documents {
+        tokens: tokens.into_iter(),
+        parser: Parser::new(options),
+        last_position: Position::start(),
+        done: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{TokenStream, parse_file_with};
+
+    fn both_ways(input: &str, options: &ParseOptions) -> (Result<JsonValue, ParseError>, Result<JsonValue, ParseError>) {
+        let tokens = parse_file_with(input.as_bytes(), options).and_then(|tokens| parse_tokens_with(&tokens, options));
+        let stream = parse_stream(TokenStream::new(input.as_bytes(), options), options);
+        (tokens, stream)
+    }
+
+    #[test]
+    fn parse_stream_matches_parse_tokens() {
+        let inputs = [
+            r#"{"pairs":[{"x0":1.5,"y0":-2,"x1":3e2,"y1":0},[1,2,3,4]]}"#,
+            r#"[true, false, null, "a\u00e9\n", {"nested": {"deep": [[], {}]}}]"#,
+            r#"{"a": 1, "a": 2, "b": "x"}"#,
+            "  42  ",
+            r#""just a string""#,
+        ];
+        for options in [ParseOptions::default(), ParseOptions { strict: true, ..ParseOptions::default() }] {
+            for input in inputs {
+                let (tokens, stream) = both_ways(input, &options);
+                assert_eq!(tokens.unwrap(), stream.unwrap(), "{input}");
             }
-            output.push(']');
-            output
+        }
+    }
+
+    #[test]
+    fn parse_stream_reports_the_same_errors() {
+        let strict = ParseOptions { strict: true, ..ParseOptions::default() };
+        for input in [r#"{"a": }"#, r#"[1, 2"#, r#"{"a" 1}"#, "[1] 2", ""] {
+            let (tokens, stream) = both_ways(input, &strict);
+            assert_eq!(tokens.unwrap_err().to_string(), stream.unwrap_err().to_string(), "{input}");
         }
     }
 }
@@ -65,9 +65,18 @@ impl RecordKeeper {
             let inclusive_ms = inclusive_duration as f64 * 1000.0 / cpu_freq as f64;
             let exclusive_ms = exclusive_duration as f64 * 1000.0 / cpu_freq as f64;
 
+            let bandwidth = if record.processed_bytes > 0 && inclusive_duration > 0 {
+                let megabytes = record.processed_bytes as f64 / 1e6;
+                let gigabytes_per_second =
+                    record.processed_bytes as f64 / 1e9 / (inclusive_duration as f64 / cpu_freq as f64);
+                format!(" | {:.3} MB at {:.2} GB/s", megabytes, gigabytes_per_second)
+            } else {
+                String::new()
+            };
+
             println!(
-                "- {:<30} | Inclusive: {:>8.2} ms ({:>5.1}%) | Exclusive: {:>8.2} ms ({:>5.1}%)",
-                record.name, inclusive_ms, inclusive_percentage, exclusive_ms, exclusive_percentage
+                "- {:<30} | Inclusive: {:>8.2} ms ({:>5.1}%) | Exclusive: {:>8.2} ms ({:>5.1}%){}",
+                record.name, inclusive_ms, inclusive_percentage, exclusive_ms, exclusive_percentage, bandwidth
             );
         }
 
@@ -84,6 +93,7 @@ pub struct BlockProfiler {
     idx: usize,
     child_duration: u64,
     duration: u64,
+    processed_bytes: u64,
 }
 
 impl BlockProfiler {
@@ -98,8 +108,16 @@ impl BlockProfiler {
             idx: current_idx,
             child_duration: 0,
             duration: 0,
+            processed_bytes: 0,
         }
     }
+
+    // A profiler that also reports throughput over `processed_bytes`.
+    pub fn with_bandwidth(name: &str, processed_bytes: u64) -> Self {
+        let mut profiler = BlockProfiler::new(name);
+        profiler.processed_bytes = processed_bytes;
+        profiler
+    }
 }
 
 impl Drop for BlockProfiler {
@@ -134,3 +152,11 @@ macro_rules! profile_block {
         crate::profiler::KEEPER.insert_block_profiler(_profiler.clone());
     };
 }
+
+#[macro_export]
+macro_rules! profile_bandwidth {
+    ($name:expr, $bytes:expr) => {
+        let _profiler = $crate::profiler::BlockProfiler::with_bandwidth($name, $bytes as u64);
+        $crate::profiler::KEEPER.insert_block_profiler(_profiler.clone());
+    };
+}
@@ -0,0 +1,207 @@
+use crate::error::ParseError;
+use crate::events::{Event, EventParser};
+use crate::lexer::{Lexeme, Lexer};
+use crate::parser::{JsonValue, ParseOptions, Parser};
+
+// Incremental parser for input that arrives in pieces of any size, such as reads from a pipe or
+// socket. A chunk may end anywhere, including inside a string, an escape or a number: the lexer
+// keeps the partial token until a later chunk completes it. With `documents`, consecutive root
+// values are parsed as separate documents; otherwise anything after the first root value is an
+// error in strict mode and ignored in lenient mode, as with the other parsers.
+pub struct PushParser {
+    lexer: Lexer,
+    events: EventParser,
+    lexemes: Vec<Lexeme>,
+    documents: bool,
+}
+
+impl PushParser {
+    pub fn new(options: &ParseOptions, documents: bool) -> Self {
+        PushParser {
+            lexer: Lexer::with_options(options),
+            events: EventParser::new(options),
+            lexemes: Vec::new(),
+            documents,
+        }
+    }
+
+    // Appends the events completed by `chunk` to `output`.
+    pub fn feed(&mut self, chunk: &[u8], output: &mut Vec<Event<'static>>) -> Result<(), ParseError> {
+        for &byte in chunk {
+            self.lexer.push_byte(byte, &mut self.lexemes)?;
+        }
+        self.push_lexemes(output)
+    }
+
+    // Ends the input, flushing a trailing number or literal, and fails if it stopped partway
+    // through a value.
+    pub fn finish(&mut self, output: &mut Vec<Event<'static>>) -> Result<(), ParseError> {
+        self.lexer.finish(&mut self.lexemes)?;
+        self.push_lexemes(output)?;
+        let ended_cleanly = self.events.is_complete() || (self.documents && self.events.is_between_documents());
+        if !ended_cleanly {
+            return Err(ParseError::new(self.lexer.position(), "end of input", self.events.expected()));
+        }
+        Ok(())
+    }
+
+    fn push_lexemes(&mut self, output: &mut Vec<Event<'static>>) -> Result<(), ParseError> {
+        for Lexeme { token, position } in self.lexemes.drain(..) {
+            if self.documents && self.events.is_complete() {
+                self.events.start_next_document();
+            }
+            if let Some(event) = self.events.push_token(token.into_ref(), || position)? {
+                output.push(event);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Like `PushParser`, but hands out each root value whole once its last token has arrived.
+pub struct PushValueParser {
+    lexer: Lexer,
+    parser: Parser,
+    lexemes: Vec<Lexeme>,
+}
+
+impl PushValueParser {
+    pub fn new(options: &ParseOptions) -> Self {
+        PushValueParser {
+            lexer: Lexer::with_options(options),
+            parser: Parser::new(options),
+            lexemes: Vec::new(),
+        }
+    }
+
+    // Appends the root values completed by `chunk` to `output`.
+    pub fn feed(&mut self, chunk: &[u8], output: &mut Vec<JsonValue>) -> Result<(), ParseError> {
+        for &byte in chunk {
+            self.lexer.push_byte(byte, &mut self.lexemes)?;
+        }
+        self.push_lexemes(output)
+    }
+
+    // Ends the input, flushing a trailing number or literal, and fails if it stopped partway
+    // through a value.
+    pub fn finish(&mut self, output: &mut Vec<JsonValue>) -> Result<(), ParseError> {
+        self.lexer.finish(&mut self.lexemes)?;
+        self.push_lexemes(output)?;
+        if !self.parser.is_between_documents() {
+            return Err(ParseError::new(self.lexer.position(), "end of input", self.parser.expected()));
+        }
+        Ok(())
+    }
+
+    fn push_lexemes(&mut self, output: &mut Vec<JsonValue>) -> Result<(), ParseError> {
+        for Lexeme { token, position } in self.lexemes.drain(..) {
+            self.parser.push_token(token.into_ref(), || position)?;
+            if let Some(document) = self.parser.take_document() {
+                output.push(document);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::parse_events;
+    use crate::lexer::TokenStream;
+    use crate::parser::parse_documents;
+
+    // Numbers, escapes (including a surrogate pair), multi-byte UTF-8 and literals, so that some
+    // split lands inside each kind of token.
+    const DOCUMENT: &str = r#"{"pairs": [{"x0": -12.5e-3, "y0": 1E+2, "x1": 0, "y1": 123456789.0625}],
+        "name": "tab\there \"q\" \\ é😀 \u00e9\ud83d\ude00", "ok": true, "no": false, "none": null}"#;
+
+    fn strict() -> ParseOptions {
+        ParseOptions { strict: true, ..ParseOptions::default() }
+    }
+
+    fn push_events(chunks: &[&[u8]], options: &ParseOptions, documents: bool) -> Result<Vec<Event<'static>>, ParseError> {
+        let mut parser = PushParser::new(options, documents);
+        let mut events = Vec::new();
+        for chunk in chunks {
+            parser.feed(chunk, &mut events)?;
+        }
+        parser.finish(&mut events)?;
+        Ok(events)
+    }
+
+    fn push_values(chunks: &[&[u8]], options: &ParseOptions) -> Result<Vec<JsonValue>, ParseError> {
+        let mut parser = PushValueParser::new(options);
+        let mut values = Vec::new();
+        for chunk in chunks {
+            parser.feed(chunk, &mut values)?;
+        }
+        parser.finish(&mut values)?;
+        Ok(values)
+    }
+
+    #[test]
+    fn events_are_the_same_for_every_split() {
+        for options in [strict(), ParseOptions::default()] {
+            let whole: Vec<Event> = parse_events(TokenStream::new(DOCUMENT.as_bytes(), &options), &options)
+                .collect::<Result<_, _>>()
+                .unwrap();
+            let bytes = DOCUMENT.as_bytes();
+            for split in 0..=bytes.len() {
+                let (head, tail) = bytes.split_at(split);
+                assert_eq!(push_events(&[head, tail], &options, false).unwrap(), whole, "split at {split}");
+            }
+            let single_bytes: Vec<&[u8]> = bytes.chunks(1).collect();
+            assert_eq!(push_events(&single_bytes, &options, false).unwrap(), whole);
+        }
+    }
+
+    #[test]
+    fn documents_are_the_same_for_every_split() {
+        let input = format!("{} 42 \"s\\u00e9\"[1,2]{{}}\n-0.5e1 true null {}", DOCUMENT, DOCUMENT);
+        let bytes = input.as_bytes();
+        let options = strict();
+        let whole: Vec<JsonValue> = parse_documents(TokenStream::new(bytes, &options), &options)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(whole.len(), 9);
+        let whole_events = push_events(&[bytes], &options, true).unwrap();
+        for split in 0..=bytes.len() {
+            let (head, tail) = bytes.split_at(split);
+            assert_eq!(push_values(&[head, tail], &options).unwrap(), whole, "split at {split}");
+            assert_eq!(push_events(&[head, tail], &options, true).unwrap(), whole_events, "split at {split}");
+        }
+        for size in [1, 2, 3, 7, 64] {
+            let chunks: Vec<&[u8]> = bytes.chunks(size).collect();
+            assert_eq!(push_values(&chunks, &options).unwrap(), whole, "chunks of {size}");
+        }
+    }
+
+    #[test]
+    fn one_document_unless_asked_for_more() {
+        let bytes = br#"{"pairs": []} {"pairs": [[1, 2, 3, 4]]}"#;
+        for split in 0..=bytes.len() {
+            let (head, tail) = bytes.split_at(split);
+            let err = push_events(&[head, tail], &strict(), false).unwrap_err();
+            assert_eq!((err.position.offset, err.expected.as_str()), (14, "end of input"), "split at {split}");
+
+            let lenient = push_events(&[head, tail], &ParseOptions::default(), false).unwrap();
+            assert_eq!(lenient.len(), 5, "split at {split}");
+            assert_eq!(push_events(&[head, tail], &strict(), true).unwrap().len(), 16, "split at {split}");
+        }
+    }
+
+    #[test]
+    fn input_ending_inside_a_token_is_an_error() {
+        for input in [r#"{"a": "unterminated"#, r#"{"a": "\u00"#, r#"{"a": 1"#, r#"[tru"#, ""] {
+            let bytes = input.as_bytes();
+            for split in 0..=bytes.len() {
+                let (head, tail) = bytes.split_at(split);
+                assert!(push_events(&[head, tail], &strict(), false).is_err(), "{input} split at {split}");
+            }
+        }
+        // Between documents is a clean end when several are expected.
+        assert_eq!(push_events(&[b"1 2 "], &strict(), true).unwrap(), [Event::Number(1.0), Event::Number(2.0)]);
+        assert!(push_values(&[b""], &strict()).unwrap().is_empty());
+    }
+}
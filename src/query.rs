@@ -0,0 +1,331 @@
+use std::fmt;
+
+use crate::parser::JsonValue;
+
+// One step of a path query.
+#[derive(Debug)]
+enum Segment {
+    Key(String),
+    Index(usize),
+    // Every element of an array or every value of an object.
+    Wildcard,
+}
+
+#[derive(Debug)]
+pub struct QueryError {
+    // Byte offset into the query text.
+    offset: usize,
+    message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+// What pointer and path lookups need from a document tree, so both `JsonValue` and the arena
+// DOM can be queried the same way.
+pub trait QueryNode<'a>: Copy + 'a {
+    fn is_array(self) -> bool;
+    // The value under `key` if this is an object holding it.
+    fn member(self, key: &str) -> Option<Self>;
+    // The element at `index` if this is an array that long.
+    fn element(self, index: usize) -> Option<Self>;
+    // Array elements or object values in order; nothing for scalars.
+    fn children(self) -> Box<dyn Iterator<Item = Self> + 'a>;
+
+    // RFC 6901 JSON Pointer lookup: `""` is the whole document and `/pairs/42/x0` walks one key
+    // or array index per `/`. `~1` stands for `/` and `~0` for `~` inside a key.
+    fn pointer(self, pointer: &str) -> Option<Self> {
+        if pointer.is_empty() {
+            return Some(self);
+        }
+        let rest = pointer.strip_prefix('/')?;
+
+        let mut node = self;
+        for token in rest.split('/') {
+            let token = token.replace("~1", "/").replace("~0", "~");
+            node = if node.is_array() {
+                node.element(parse_array_index(&token)?)?
+            } else {
+                node.member(&token)?
+            };
+        }
+        Some(node)
+    }
+
+    // Every node matched by a dotted path such as `pairs[*].x0`, in document order.
+    //
+    // Segments are `.key` (or a bare key at the start), `[index]`, `[*]` and `.*`; a leading `$`
+    // is accepted so paths printed in error messages can be pasted back in.
+    fn query(self, path: &str) -> Result<Query<'a, Self>, QueryError> {
+        let segments = parse_path(path)?;
+        Ok(Query {
+            segments,
+            stack: vec![(Children::One(Some(self)), 0)],
+        })
+    }
+}
+
+impl<'a> QueryNode<'a> for &'a JsonValue {
+    fn is_array(self) -> bool {
+        matches!(self, JsonValue::Array(_))
+    }
+
+    fn member(self, key: &str) -> Option<Self> {
+        self.get(key)
+    }
+
+    fn element(self, index: usize) -> Option<Self> {
+        self.get_index(index)
+    }
+
+    fn children(self) -> Box<dyn Iterator<Item = Self> + 'a> {
+        match self {
+            JsonValue::Array(values) => Box::new(values.iter()),
+            JsonValue::Object(map) => Box::new(map.values()),
+            _ => Box::new(std::iter::empty()),
+        }
+    }
+}
+
+// RFC 6901 array indices are decimal without leading zeros; `-` (one past the end) never matches.
+fn parse_array_index(token: &str) -> Option<usize> {
+    if token.is_empty() || (token.len() > 1 && token.starts_with('0')) {
+        return None;
+    }
+    if !token.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+fn parse_path(path: &str) -> Result<Vec<Segment>, QueryError> {
+    let bytes = path.as_bytes();
+    let error = |offset: usize, message: &str| QueryError {
+        offset,
+        message: message.to_string(),
+    };
+
+    let mut segments = Vec::new();
+    let mut i = 0;
+    if path == "$" || path.starts_with("$.") || path.starts_with("$[") {
+        i = 1;
+    }
+    while i < bytes.len() {
+        match bytes[i] {
+            b'[' => {
+                let Some(length) = bytes[i..].iter().position(|&byte| byte == b']') else {
+                    return Err(error(i, "unclosed '['"));
+                };
+                let inner = &path[i + 1..i + length];
+                let segment = if inner == "*" {
+                    Segment::Wildcard
+                } else {
+                    match inner.parse() {
+                        Ok(index) => Segment::Index(index),
+                        Err(_) => return Err(error(i + 1, "expected an array index or '*'")),
+                    }
+                };
+                segments.push(segment);
+                i += length + 1;
+            }
+            byte => {
+                // A key needs a leading '.' unless it starts the path.
+                let start = if byte == b'.' {
+                    i + 1
+                } else if i == 0 {
+                    i
+                } else {
+                    return Err(error(i, "expected '.' or '['"));
+                };
+                let length = bytes[start..]
+                    .iter()
+                    .position(|&byte| byte == b'.' || byte == b'[')
+                    .unwrap_or(bytes.len() - start);
+                if length == 0 {
+                    return Err(error(start, "expected a key"));
+                }
+                let key = &path[start..start + length];
+                segments.push(if key == "*" {
+                    Segment::Wildcard
+                } else {
+                    Segment::Key(key.to_string())
+                });
+                i = start + length;
+            }
+        }
+    }
+    Ok(segments)
+}
+
+enum Children<'a, N> {
+    One(Option<N>),
+    Many(Box<dyn Iterator<Item = N> + 'a>),
+}
+
+impl<'a, N: QueryNode<'a>> Iterator for Children<'a, N> {
+    type Item = N;
+
+    fn next(&mut self) -> Option<N> {
+        match self {
+            Children::One(node) => node.take(),
+            Children::Many(nodes) => nodes.next(),
+        }
+    }
+}
+
+// Lazily walks the document; only wildcards keep an iterator on the stack, so a query over a
+// large array holds one entry per wildcard rather than one per match.
+pub struct Query<'a, N> {
+    segments: Vec<Segment>,
+    // Candidates still to visit, each with the number of segments already applied to reach it.
+    stack: Vec<(Children<'a, N>, usize)>,
+}
+
+impl<'a, N: QueryNode<'a>> Iterator for Query<'a, N> {
+    type Item = N;
+
+    fn next(&mut self) -> Option<N> {
+        'candidates: loop {
+            let (children, depth) = self.stack.last_mut()?;
+            let depth = *depth;
+            let Some(mut node) = children.next() else {
+                self.stack.pop();
+                continue;
+            };
+
+            for (applied, segment) in self.segments.iter().enumerate().skip(depth) {
+                node = match segment {
+                    Segment::Key(key) => match node.member(key) {
+                        Some(child) => child,
+                        None => continue 'candidates,
+                    },
+                    Segment::Index(index) => match node.element(*index) {
+                        Some(child) => child,
+                        None => continue 'candidates,
+                    },
+                    Segment::Wildcard => {
+                        self.stack.push((Children::Many(node.children()), applied + 1));
+                        continue 'candidates;
+                    }
+                };
+            }
+            return Some(node);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::{ArenaValue, JsonArena, parse_arena};
+    use crate::lexer::{TokenStream, parse_file_with};
+    use crate::parser::{ParseOptions, parse_tokens_with};
+
+    const DOCUMENT: &str = r#"{
+        "a/b": 1, "m~n": 2, "~1": 3, "": {"": 4}, "0": "zero",
+        "list": [10, 20, {"x": 30}],
+        "nested": {"list": [{"x": 1}, {"y": 2}, {"x": 3}]}
+    }"#;
+
+    fn parse(input: &str) -> JsonValue {
+        let options = ParseOptions::default();
+        parse_file_with(input.as_bytes(), &options).and_then(|tokens| parse_tokens_with(&tokens, &options)).unwrap()
+    }
+
+    fn document() -> (JsonValue, JsonArena) {
+        let options = ParseOptions::default();
+        let arena = parse_arena(TokenStream::new(DOCUMENT.as_bytes(), &options), &options).unwrap();
+        (parse(DOCUMENT), arena)
+    }
+
+    // Looks `pointer` up in the tree and the arena, which must agree.
+    fn pointer(pointer: &str) -> Option<JsonValue> {
+        let (tree, arena) = document();
+        let from_tree = (&tree).pointer(pointer).cloned();
+        assert_eq!(arena.root().pointer(pointer).map(ArenaValue::to_json_value), from_tree, "{pointer}");
+        from_tree
+    }
+
+    // Runs `path` over the tree and the arena, which must match the same nodes in the same order.
+    fn query(path: &str) -> Result<Vec<JsonValue>, (usize, String)> {
+        let (tree, arena) = document();
+        let from_tree = (&tree)
+            .query(path)
+            .map(|nodes| nodes.cloned().collect::<Vec<_>>())
+            .map_err(|err| (err.offset, err.message));
+        let from_arena = arena
+            .root()
+            .query(path)
+            .map(|nodes| nodes.map(ArenaValue::to_json_value).collect::<Vec<_>>())
+            .map_err(|err| (err.offset, err.message));
+        assert_eq!(from_arena, from_tree, "{path}");
+        from_tree
+    }
+
+    #[test]
+    fn pointers_follow_rfc_6901() {
+        for (path, expected) in [
+            ("", Some(DOCUMENT)),
+            ("/a~1b", Some("1")),
+            ("/m~0n", Some("2")),
+            ("/~01", Some("3")),
+            ("/~1", None),
+            ("/", Some(r#"{"": 4}"#)),
+            ("//", Some("4")),
+            ("/0", Some(r#""zero""#)),
+            ("/list/0", Some("10")),
+            ("/list/2/x", Some("30")),
+            ("/nested/list/2/x", Some("3")),
+            ("/list/-", None),
+            ("/list/01", None),
+            ("/list/+1", None),
+            ("/list/3", None),
+            ("/list/0/x", None),
+            ("/nested/list/1/x", None),
+            ("list", None),
+        ] {
+            assert_eq!(pointer(path), expected.map(parse), "{path}");
+        }
+    }
+
+    #[test]
+    fn paths_select_in_document_order() {
+        for (path, expected) in [
+            ("$", vec![DOCUMENT]),
+            ("list", vec!["[10, 20, {\"x\": 30}]"]),
+            ("$.list[2].x", vec!["30"]),
+            ("list[*]", vec!["10", "20", r#"{"x": 30}"#]),
+            ("$.nested.list[*].x", vec!["1", "3"]),
+            ("nested.*.*.*", vec!["1", "2", "3"]),
+            ("list[*].*", vec!["30"]),
+            ("$[*]", vec!["1", "2", "3", r#"{"": 4}"#, r#""zero""#, r#"[10, 20, {"x": 30}]"#, r#"{"list": [{"x": 1}, {"y": 2}, {"x": 3}]}"#]),
+            ("a/b", vec!["1"]),
+            ("list[3]", vec![]),
+            ("missing[*]", vec![]),
+        ] {
+            let expected: Vec<JsonValue> = expected.into_iter().map(parse).collect();
+            assert_eq!(query(path), Ok(expected), "{path}");
+        }
+    }
+
+    #[test]
+    fn malformed_paths_are_errors() {
+        for (path, offset, message) in [
+            ("list[", 4, "unclosed '['"),
+            ("list[x]", 5, "expected an array index or '*'"),
+            ("list[-1]", 5, "expected an array index or '*'"),
+            ("list[]", 5, "expected an array index or '*'"),
+            ("a..b", 2, "expected a key"),
+            ("$.", 2, "expected a key"),
+            (".", 1, "expected a key"),
+            ("list[0]x", 7, "expected '.' or '['"),
+        ] {
+            assert_eq!(query(path), Err((offset, message.to_string())), "{path}");
+        }
+    }
+}
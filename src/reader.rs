@@ -0,0 +1,277 @@
+#[cfg(target_os = "linux")]
+use std::alloc::{Layout, alloc_zeroed, dealloc};
+use std::fs::File;
+#[cfg(target_os = "linux")]
+use std::fs::OpenOptions;
+use std::io::{self, Read};
+use std::ops::Deref;
+#[cfg(target_os = "linux")]
+use std::os::unix::fs::OpenOptionsExt;
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+#[cfg(unix)]
+use std::ptr::NonNull;
+
+use crate::profile_bandwidth;
+
+// O_DIRECT transfers must start at, and be a multiple of, the device's logical block size.
+#[cfg(target_os = "linux")]
+const DIRECT_ALIGNMENT: usize = 4096;
+#[cfg(unix)]
+const PAGE_SIZE: usize = 4096;
+
+// A whole input file in memory, however it got there.
+pub enum FileBuffer {
+    Heap(Vec<u8>),
+    #[cfg(unix)]
+    Mapped(Mapping),
+    #[cfg(target_os = "linux")]
+    Aligned(AlignedBuffer),
+}
+
+impl Deref for FileBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileBuffer::Heap(bytes) => bytes,
+            #[cfg(unix)]
+            FileBuffer::Mapped(mapping) => mapping.as_slice(),
+            #[cfg(target_os = "linux")]
+            FileBuffer::Aligned(buffer) => buffer.as_slice(),
+        }
+    }
+}
+
+#[cfg(unix)]
+pub struct Mapping {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+#[cfg(unix)]
+impl Mapping {
+    fn as_slice(&self) -> &[u8] {
+        // SAFETY: the mapping is readable for `len` bytes until it is unmapped in `drop`.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr.as_ptr() as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub struct AlignedBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+    layout: Layout,
+}
+
+#[cfg(target_os = "linux")]
+impl AlignedBuffer {
+    fn new(capacity: usize) -> Self {
+        let layout = Layout::from_size_align(capacity.max(1), DIRECT_ALIGNMENT).unwrap();
+        // SAFETY: the layout has a non-zero size. Zeroed so the spare capacity is initialised.
+        let ptr = NonNull::new(unsafe { alloc_zeroed(layout) })
+            .unwrap_or_else(|| std::alloc::handle_alloc_error(layout));
+        AlignedBuffer { ptr, len: 0, layout }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        // SAFETY: the first `len` bytes have been filled by `read`.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    fn spare_capacity(&mut self) -> &mut [u8] {
+        // SAFETY: the whole allocation was zero-initialised and `len` never exceeds its size.
+        unsafe {
+            std::slice::from_raw_parts_mut(self.ptr.as_ptr().add(self.len), self.layout.size() - self.len)
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.ptr.as_ptr(), self.layout) }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn round_up(value: usize, alignment: usize) -> usize {
+    value.div_ceil(alignment) * alignment
+}
+
+// One `read_to_end` into a buffer sized from the file's metadata, so it never reallocates.
+pub fn read_to_end(filename: &str) -> io::Result<FileBuffer> {
+    let mut file = File::open(filename)?;
+    let size = file.metadata()?.len() as usize;
+    profile_bandwidth!("Read (read_to_end)", size);
+
+    let mut bytes = Vec::with_capacity(size);
+    file.read_to_end(&mut bytes)?;
+    Ok(FileBuffer::Heap(bytes))
+}
+
+// Reads through a fixed `chunk_size` buffer and appends each chunk, like a streaming consumer
+// would see the file.
+pub fn read_chunked(filename: &str, chunk_size: usize) -> io::Result<FileBuffer> {
+    let mut file = File::open(filename)?;
+    let size = file.metadata()?.len() as usize;
+    profile_bandwidth!("Read (chunked)", size);
+
+    let mut bytes = Vec::with_capacity(size);
+    let mut chunk = vec![0u8; chunk_size.max(1)];
+    loop {
+        let count = match file.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(count) => count,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        bytes.extend_from_slice(&chunk[..count]);
+    }
+    Ok(FileBuffer::Heap(bytes))
+}
+
+// Maps the file read-only and advises the kernel it will be read once, front to back.
+#[cfg(unix)]
+pub fn map_file(filename: &str) -> io::Result<FileBuffer> {
+    let file = File::open(filename)?;
+    let size = file.metadata()?.len() as usize;
+    profile_bandwidth!("Read (mmap)", size);
+
+    if size == 0 {
+        return Ok(FileBuffer::Heap(Vec::new()));
+    }
+    let ptr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            size,
+            libc::PROT_READ,
+            libc::MAP_PRIVATE,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+    unsafe {
+        // Hints only; the mapping works the same if the kernel ignores them.
+        libc::madvise(ptr, size, libc::MADV_SEQUENTIAL);
+        libc::madvise(ptr, size, libc::MADV_WILLNEED);
+    }
+    let mapping = Mapping {
+        ptr: NonNull::new(ptr as *mut u8).unwrap(),
+        len: size,
+    };
+    // Fault every page in now so the time shows up here rather than in the lexer.
+    for page in mapping.as_slice().iter().step_by(PAGE_SIZE) {
+        unsafe { std::ptr::read_volatile(page) };
+    }
+    Ok(FileBuffer::Mapped(mapping))
+}
+
+#[cfg(not(unix))]
+pub fn map_file(_filename: &str) -> io::Result<FileBuffer> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "--read mmap is only available on Unix"))
+}
+
+// Reads with O_DIRECT, bypassing the page cache, in block-aligned chunks of about `chunk_size`
+// straight into an aligned buffer.
+#[cfg(target_os = "linux")]
+pub fn read_direct(filename: &str, chunk_size: usize) -> io::Result<FileBuffer> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_DIRECT)
+        .open(filename)?;
+    let size = file.metadata()?.len() as usize;
+    profile_bandwidth!("Read (O_DIRECT)", size);
+
+    let chunk_size = round_up(chunk_size.max(1), DIRECT_ALIGNMENT);
+    let mut buffer = AlignedBuffer::new(round_up(size, DIRECT_ALIGNMENT));
+    loop {
+        let spare = buffer.spare_capacity();
+        if spare.is_empty() {
+            break;
+        }
+        let request = spare.len().min(chunk_size);
+        let count = match file.read(&mut spare[..request]) {
+            Ok(0) => break,
+            Ok(count) => count,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        buffer.len += count;
+        // A short read means end of file; the offset is no longer aligned for another.
+        if count < request {
+            break;
+        }
+    }
+    Ok(FileBuffer::Aligned(buffer))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_direct(_filename: &str, _chunk_size: usize) -> io::Result<FileBuffer> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "--read direct (O_DIRECT) is only available on Linux"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn every_strategy_reads_the_same_bytes() {
+        let mut rng_gen = SmallRng::seed_from_u64(40);
+        for size in [0, 1, 100, 4095, 4096, 4097, 12_345, 3 * 4096, 70_001] {
+            let bytes: Vec<u8> = (0..size).map(|_| rng_gen.random()).collect();
+            let path = std::env::temp_dir().join(format!("haversine-{}-read-{}.bin", std::process::id(), size));
+            std::fs::write(&path, &bytes).unwrap();
+            let filename = path.to_str().unwrap();
+
+            let mut buffers = vec![("read_to_end".to_string(), read_to_end(filename))];
+            for chunk_size in [1, 1000, 4096, 1 << 20] {
+                buffers.push((format!("chunked {}", chunk_size), read_chunked(filename, chunk_size)));
+            }
+            if cfg!(unix) {
+                buffers.push(("mmap".to_string(), map_file(filename)));
+            }
+            if cfg!(target_os = "linux") {
+                for chunk_size in [1, 5000, 1 << 20] {
+                    match read_direct(filename, chunk_size) {
+                        // Filesystems such as tmpfs refuse O_DIRECT when the file is opened.
+                        Err(err) if err.kind() == io::ErrorKind::InvalidInput => break,
+                        result => buffers.push((format!("direct {}", chunk_size), result)),
+                    }
+                }
+            }
+
+            for (strategy, buffer) in buffers {
+                assert!(buffer.unwrap()[..] == bytes[..], "{strategy} read of {size} bytes");
+            }
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn missing_files_are_errors() {
+        let filename = "/nonexistent/haversine-input.json";
+        assert_eq!(read_to_end(filename).err().unwrap().kind(), io::ErrorKind::NotFound);
+        assert_eq!(read_chunked(filename, 4096).err().unwrap().kind(), io::ErrorKind::NotFound);
+        if cfg!(unix) {
+            assert_eq!(map_file(filename).err().unwrap().kind(), io::ErrorKind::NotFound);
+        }
+        if cfg!(target_os = "linux") {
+            assert_eq!(read_direct(filename, 4096).err().unwrap().kind(), io::ErrorKind::NotFound);
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::io::{self, Write};
+
+use crate::parser::JsonValue;
+
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    // Spaces per nesting level; `None` writes everything on one line with no spaces.
+    pub indent: Option<usize>,
+    // Write object keys in byte order instead of insertion order.
+    pub sort_keys: bool,
+}
+
+pub fn write_json<W: Write>(
+    output: &mut W,
+    value: &JsonValue,
+    options: &WriteOptions,
+) -> io::Result<()> {
+    write_value(output, value, options, 0)
+}
+
+fn write_value<W: Write>(
+    output: &mut W,
+    value: &JsonValue,
+    options: &WriteOptions,
+    depth: usize,
+) -> io::Result<()> {
+    match value {
+        JsonValue::String(s) => write_string(output, s),
+        JsonValue::Number(n) => write_number(output, *n),
+        JsonValue::Bool(b) => write!(output, "{}", b),
+        JsonValue::Null => output.write_all(b"null"),
+        JsonValue::Array(values) => {
+            if values.is_empty() {
+                return output.write_all(b"[]");
+            }
+            output.write_all(b"[")?;
+            for (i, value) in values.iter().enumerate() {
+                if i != 0 {
+                    output.write_all(b",")?;
+                }
+                write_newline(output, options, depth + 1)?;
+                write_value(output, value, options, depth + 1)?;
+            }
+            write_newline(output, options, depth)?;
+            output.write_all(b"]")
+        }
+        JsonValue::Object(map) => {
+            if map.is_empty() {
+                return output.write_all(b"{}");
+            }
+            let mut entries: Vec<_> = map.iter().collect();
+            if options.sort_keys {
+                // Stable, so repeated keys keep their relative order.
+                entries.sort_by_key(|&(key, _)| key);
+            }
+            output.write_all(b"{")?;
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i != 0 {
+                    output.write_all(b",")?;
+                }
+                write_newline(output, options, depth + 1)?;
+                write_string(output, key)?;
+                output.write_all(if options.indent.is_some() { b": " } else { b":" })?;
+                write_value(output, value, options, depth + 1)?;
+            }
+            write_newline(output, options, depth)?;
+            output.write_all(b"}")
+        }
+    }
+}
+
+fn write_newline<W: Write>(output: &mut W, options: &WriteOptions, depth: usize) -> io::Result<()> {
+    let Some(indent) = options.indent else {
+        return Ok(());
+    };
+    write!(output, "\n{:width$}", "", width = indent * depth)
+}
+
+// Shortest text that parses back to the same bits. Integral values are written without a
+// fraction; infinities and NaN have no JSON representation and become `null`.
+fn write_number<W: Write>(output: &mut W, n: f64) -> io::Result<()> {
+    if !n.is_finite() {
+        output.write_all(b"null")
+    } else if n.fract() == 0.0 && n.abs() < 1e16 {
+        write!(output, "{}", n)
+    } else {
+        write!(output, "{:?}", n)
+    }
+}
+
+fn write_string<W: Write>(output: &mut W, s: &str) -> io::Result<()> {
+    output.write_all(b"\"")?;
+    let bytes = s.as_bytes();
+    let mut unescaped_from = 0;
+    for (i, &byte) in bytes.iter().enumerate() {
+        let escape: &[u8] = match byte {
+            b'"' => b"\\\"",
+            b'\\' => b"\\\\",
+            b'\n' => b"\\n",
+            b'\r' => b"\\r",
+            b'\t' => b"\\t",
+            0x08 => b"\\b",
+            0x0C => b"\\f",
+            0x00..=0x1F => b"",
+            _ => continue,
+        };
+        output.write_all(&bytes[unescaped_from..i])?;
+        if escape.is_empty() {
+            write!(output, "\\u{:04x}", byte)?;
+        } else {
+            output.write_all(escape)?;
+        }
+        unescaped_from = i + 1;
+    }
+    output.write_all(&bytes[unescaped_from..])?;
+    output.write_all(b"\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::parse_file_with;
+    use crate::parser::{DuplicateKeys, ParseOptions, parse_tokens_with};
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    fn format(value: &JsonValue, options: &WriteOptions) -> String {
+        let mut output = Vec::new();
+        write_json(&mut output, value, options).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn parse(input: &str) -> JsonValue {
+        let options = ParseOptions { strict: true, duplicate_keys: DuplicateKeys::KeepAll };
+        let tokens = parse_file_with(input.as_bytes(), &options).unwrap();
+        parse_tokens_with(&tokens, &options).unwrap()
+    }
+
+    // Writes `input` back out in both layouts and checks each parses to the same value.
+    fn assert_round_trips(input: &str) -> JsonValue {
+        let value = parse(input);
+        for indent in [None, Some(2)] {
+            let text = format(&value, &WriteOptions { indent, sort_keys: false });
+            assert_eq!(parse(&text), value, "{input} -> {text}");
+        }
+        value
+    }
+
+    #[test]
+    fn escapes_round_trip() {
+        let value = assert_round_trips(r#"["quote \" backslash \\ slash \/", "\b\f\n\r\t", "\u0000\u001f\u007f"]"#);
+        assert_eq!(
+            format(&value, &WriteOptions::default()),
+            "[\"quote \\\" backslash \\\\ slash /\",\"\\b\\f\\n\\r\\t\",\"\\u0000\\u001f\u{7f}\"]"
+        );
+    }
+
+    #[test]
+    fn non_bmp_characters_round_trip() {
+        let value = assert_round_trips(r#"{"emoji": "\ud83d\ude00 😀", "accents": "héllo", "cjk": "漢字"}"#);
+        assert_eq!(value["emoji"].as_str(), Some("😀 😀"));
+        // Written as UTF-8, not re-escaped.
+        assert!(format(&value, &WriteOptions::default()).contains("😀 😀"));
+    }
+
+    #[test]
+    fn numbers_use_the_shortest_representation() {
+        for (n, text) in [
+            (0.1, "0.1"),
+            (-2.5, "-2.5"),
+            (100.0, "100"),
+            (-0.0, "-0"),
+            (1e16, "1e16"),
+            (1e300, "1e300"),
+            (5e-324, "5e-324"),
+            (1.0 / 3.0, "0.3333333333333333"),
+            (f64::INFINITY, "null"),
+            (f64::NAN, "null"),
+        ] {
+            assert_eq!(format(&JsonValue::Number(n), &WriteOptions::default()), text);
+        }
+
+        let mut rng = SmallRng::seed_from_u64(44);
+        for _ in 0..10_000 {
+            let n = f64::from_bits(rng.random());
+            if !n.is_finite() {
+                continue;
+            }
+            let text = format(&JsonValue::Number(n), &WriteOptions::default());
+            assert_eq!(parse(&text).as_f64().map(f64::to_bits), Some(n.to_bits()), "{text}");
+        }
+    }
+
+    #[test]
+    fn indent_and_sort_keys() {
+        let value = assert_round_trips(r#"{"b": [1, {"d": null, "c": true}], "a": {}, "b": []}"#);
+        let sorted = WriteOptions { indent: Some(2), sort_keys: true };
+        assert_eq!(
+            format(&value, &sorted),
+            "{\n  \"a\": {},\n  \"b\": [\n    1,\n    {\n      \"c\": true,\n      \"d\": null\n    }\n  ],\n  \"b\": []\n}"
+        );
+        assert_eq!(format(&value, &WriteOptions::default()), r#"{"b":[1,{"d":null,"c":true}],"a":{},"b":[]}"#);
+        // Sorting only reorders members, so each key still maps to the same values.
+        let resorted = parse(&format(&value, &sorted));
+        assert_eq!(resorted["b"], value["b"]);
+        assert_eq!(resorted["a"], value["a"]);
+    }
+}
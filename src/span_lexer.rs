@@ -0,0 +1,293 @@
+use std::borrow::Cow;
+
+use crate::error::{ParseError, Position};
+use crate::events::{Event, EventParser};
+use crate::float::parse_f64;
+use crate::lexer::{TokenRef, decode_string, describe_byte, is_rfc8259_number};
+use crate::parser::{JsonValue, ParseOptions, Parser};
+use crate::profile_block;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TokenKind {
+    OpenBrace,
+    CloseBrace,
+    OpenBracket,
+    CloseBracket,
+    Comma,
+    Colon,
+    String,
+    EscapedString,
+    Number,
+    True,
+    False,
+    Null,
+}
+
+// A token as a tag plus the byte range it covers in the input buffer. String spans exclude the
+// quotes; strings containing a backslash are tagged so only those need decoding.
+#[derive(Debug, Clone, Copy)]
+pub struct SpanToken {
+    pub kind: TokenKind,
+    pub start: u32,
+    pub end: u32,
+}
+
+impl SpanToken {
+    pub fn bytes<'a>(&self, buffer: &'a [u8]) -> &'a [u8] {
+        &buffer[self.start as usize..self.end as usize]
+    }
+
+    // Turns the span into the token the parser consumes, borrowing string contents from `buffer`
+    // unless they contain escapes.
+    pub fn resolve<'a>(&self, buffer: &'a [u8]) -> Result<TokenRef<'a>, ParseError> {
+        let bytes = self.bytes(buffer);
+        let start = self.start as usize;
+        let token = match self.kind {
+            TokenKind::OpenBrace => TokenRef::OpenBrace,
+            TokenKind::CloseBrace => TokenRef::CloseBrace,
+            TokenKind::OpenBracket => TokenRef::OpenBracket,
+            TokenKind::CloseBracket => TokenRef::CloseBracket,
+            TokenKind::Comma => TokenRef::Comma,
+            TokenKind::Colon => TokenRef::Colon,
+            TokenKind::String => match std::str::from_utf8(bytes) {
+                Ok(s) => TokenRef::StringContent(Cow::Borrowed(s)),
+                // Decoded again only so the bad sequence is described the way the scalar lexer
+                // describes it.
+                Err(_) => decode_span(buffer, bytes, start)?,
+            },
+            TokenKind::EscapedString => decode_span(buffer, bytes, start)?,
+            TokenKind::Number => {
+                let Some(n) = parse_f64(bytes) else {
+                    return Err(ParseError::new(
+                        Position::locate(buffer, start),
+                        format!("'{}'", String::from_utf8_lossy(bytes)),
+                        "a number",
+                    ));
+                };
+                TokenRef::Number(n)
+            }
+            TokenKind::True => TokenRef::Boolean(true),
+            TokenKind::False => TokenRef::Boolean(false),
+            TokenKind::Null => TokenRef::Null,
+        };
+        Ok(token)
+    }
+}
+
+fn decode_span(buffer: &[u8], bytes: &[u8], start: usize) -> Result<TokenRef<'static>, ParseError> {
+    match decode_string(bytes, start) {
+        Ok(s) => Ok(TokenRef::StringContent(Cow::Owned(s))),
+        Err(err) => Err(ParseError::new(Position::locate(buffer, err.offset), err.found, err.expected)),
+    }
+}
+
+fn is_number_byte(byte: u8) -> bool {
+    matches!(byte, b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E')
+}
+
+// Tokenizes a whole in-memory document without allocating per token.
+pub fn tokenize(buffer: &[u8], options: &ParseOptions) -> Result<Vec<SpanToken>, ParseError> {
+    profile_block!("Tokenize buffer");
+    if buffer.len() > u32::MAX as usize {
+        return Err(ParseError::new(
+            Position::start(),
+            format!("{} bytes of input", buffer.len()),
+            "at most 4 GiB for span tokens",
+        ));
+    }
+
+    let error = |offset: usize, found: String, expected: &str| {
+        Err(ParseError::new(Position::locate(buffer, offset), found, expected))
+    };
+
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < buffer.len() {
+        let start = i;
+        let byte = buffer[i];
+        let kind = match byte {
+            b'{' => TokenKind::OpenBrace,
+            b'}' => TokenKind::CloseBrace,
+            b'[' => TokenKind::OpenBracket,
+            b']' => TokenKind::CloseBracket,
+            b',' => TokenKind::Comma,
+            b':' => TokenKind::Colon,
+            b'"' => {
+                let mut escaped = false;
+                i += 1;
+                loop {
+                    match buffer.get(i) {
+                        Some(b'"') => break,
+                        Some(b'\\') => {
+                            escaped = true;
+                            i += 2;
+                        }
+                        Some(&byte) if byte < 0x20 && options.strict => {
+                            return error(i, describe_byte(byte), "an escaped control character");
+                        }
+                        Some(_) => i += 1,
+                        None => {
+                            return error(
+                                buffer.len(),
+                                "end of input".to_string(),
+                                "closing '\"' of string",
+                            );
+                        }
+                    }
+                }
+                tokens.push(SpanToken {
+                    kind: if escaped { TokenKind::EscapedString } else { TokenKind::String },
+                    start: (start + 1) as u32,
+                    end: i as u32,
+                });
+                i += 1;
+                continue;
+            }
+            b' ' | b'\t' | b'\n' | b'\r' => {
+                i += 1;
+                continue;
+            }
+            _ => {
+                let (token, next) = scalar_token(buffer, i, options)?;
+                tokens.extend(token);
+                i = next;
+                continue;
+            }
+        };
+        tokens.push(SpanToken {
+            kind,
+            start: start as u32,
+            end: (start + 1) as u32,
+        });
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+// Lexes the number or literal starting at `start`, or skips a stray byte, returning the token and
+// the offset just past it.
+pub fn scalar_token(
+    buffer: &[u8],
+    start: usize,
+    options: &ParseOptions,
+) -> Result<(Option<SpanToken>, usize), ParseError> {
+    let error = |offset: usize, found: String, expected: &str| {
+        Err(ParseError::new(Position::locate(buffer, offset), found, expected))
+    };
+
+    let mut i = start;
+    let kind = match buffer[start] {
+        b'0'..=b'9' | b'-' | b'+' | b'.' => {
+            while i < buffer.len() && is_number_byte(buffer[i]) {
+                i += 1;
+            }
+            if options.strict {
+                let candidate = String::from_utf8_lossy(&buffer[start..i]);
+                if !is_rfc8259_number(&candidate) {
+                    return error(start, format!("'{}'", candidate), "an RFC 8259 number");
+                }
+            }
+            TokenKind::Number
+        }
+        b'a'..=b'z' | b'A'..=b'Z' => {
+            while i < buffer.len() && buffer[i].is_ascii_alphabetic() {
+                i += 1;
+            }
+            match &buffer[start..i] {
+                b"true" => TokenKind::True,
+                b"false" => TokenKind::False,
+                b"null" => TokenKind::Null,
+                literal => {
+                    let found = format!("'{}'", String::from_utf8_lossy(literal));
+                    return error(start, found, "true, false or null");
+                }
+            }
+        }
+        byte => {
+            if options.strict {
+                return error(start, describe_byte(byte), "a JSON token");
+            }
+            return Ok((None, start + 1));
+        }
+    };
+    let token = SpanToken {
+        kind,
+        start: start as u32,
+        end: i as u32,
+    };
+    Ok((Some(token), i))
+}
+
+pub fn parse_spans(
+    buffer: &[u8],
+    tokens: &[SpanToken],
+    options: &ParseOptions,
+) -> Result<JsonValue, ParseError> {
+    profile_block!("Parse spans");
+    let mut parser = Parser::new(options);
+
+    for token in tokens {
+        let position = || Position::locate(buffer, token.start as usize);
+        parser.push_token(token.resolve(buffer)?, position)?;
+    }
+
+    parser.finish(|| Position::locate(buffer, tokens.last().map_or(0, |token| token.start as usize)))
+}
+
+// Events for the root value in a span token buffer; keys and strings without escapes borrow
+// from `buffer`.
+pub struct SpanEvents<'a> {
+    buffer: &'a [u8],
+    tokens: std::slice::Iter<'a, SpanToken>,
+    parser: EventParser,
+    last_start: usize,
+    done: bool,
+}
+
+impl<'a> Iterator for SpanEvents<'a> {
+    type Item = Result<Event<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let buffer = self.buffer;
+        let result = loop {
+            let Some(token) = self.tokens.next() else {
+                self.done = true;
+                if self.parser.is_complete() {
+                    return None;
+                }
+                return Some(Err(ParseError::new(
+                    Position::locate(buffer, self.last_start),
+                    "end of input",
+                    self.parser.expected(),
+                )));
+            };
+            self.last_start = token.start as usize;
+            let position = || Position::locate(buffer, token.start as usize);
+            match token
+                .resolve(buffer)
+                .and_then(|resolved| self.parser.push_token(resolved, position))
+            {
+                Ok(Some(event)) => break Ok(event),
+                Ok(None) => {}
+                Err(err) => break Err(err),
+            }
+        };
+        self.done = result.is_err();
+        Some(result)
+    }
+}
+
+pub fn span_events<'a>(buffer: &'a [u8], tokens: &'a [SpanToken], options: &ParseOptions) -> SpanEvents<'a> {
+    SpanEvents {
+        buffer,
+        tokens: tokens.iter(),
+        parser: EventParser::new(options),
+        last_start: 0,
+        done: false,
+    }
+}
@@ -0,0 +1,439 @@
+// The block scanner only has x86_64 kernels; elsewhere `tokenize_simd` is `tokenize`.
+#![cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use crate::error::{ParseError, Position};
+use crate::lexer::describe_byte;
+use crate::parser::ParseOptions;
+use crate::profile_block;
+use crate::span_lexer::{SpanToken, TokenKind, scalar_token, tokenize};
+
+const ODD_BITS: u64 = 0xAAAA_AAAA_AAAA_AAAA;
+
+// Bitmasks over one 64-byte block; bit `i` describes byte `i` of the block.
+struct BlockMasks {
+    structural: u64,
+    whitespace: u64,
+    quote: u64,
+    backslash: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kernel {
+    #[cfg(target_arch = "x86_64")]
+    Avx2,
+    #[cfg(target_arch = "x86_64")]
+    Sse2,
+}
+
+impl Kernel {
+    // The widest kernel this CPU supports, if any.
+    #[cfg(target_arch = "x86_64")]
+    pub fn detect() -> Option<Kernel> {
+        if is_x86_feature_detected!("avx2") {
+            Some(Kernel::Avx2)
+        } else if is_x86_feature_detected!("sse2") {
+            Some(Kernel::Sse2)
+        } else {
+            None
+        }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    pub fn detect() -> Option<Kernel> {
+        None
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn masks_avx2(block: &[u8; 64]) -> BlockMasks {
+    let halves = unsafe {
+        [
+            _mm256_loadu_si256(block.as_ptr() as *const __m256i),
+            _mm256_loadu_si256(block.as_ptr().add(32) as *const __m256i),
+        ]
+    };
+    let matching = |bytes: &[u8]| {
+        let mut mask = 0u64;
+        for (half, &chunk) in halves.iter().enumerate() {
+            let mut hits = _mm256_setzero_si256();
+            for &byte in bytes {
+                hits =
+                    _mm256_or_si256(hits, _mm256_cmpeq_epi8(chunk, _mm256_set1_epi8(byte as i8)));
+            }
+            mask |= (_mm256_movemask_epi8(hits) as u32 as u64) << (half * 32);
+        }
+        mask
+    };
+    BlockMasks {
+        structural: matching(b"{}[],:"),
+        whitespace: matching(b" \t\n\r"),
+        quote: matching(b"\""),
+        backslash: matching(b"\\"),
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn masks_sse2(block: &[u8; 64]) -> BlockMasks {
+    let quarters = unsafe {
+        [
+            _mm_loadu_si128(block.as_ptr() as *const __m128i),
+            _mm_loadu_si128(block.as_ptr().add(16) as *const __m128i),
+            _mm_loadu_si128(block.as_ptr().add(32) as *const __m128i),
+            _mm_loadu_si128(block.as_ptr().add(48) as *const __m128i),
+        ]
+    };
+    let matching = |bytes: &[u8]| {
+        let mut mask = 0u64;
+        for (quarter, &chunk) in quarters.iter().enumerate() {
+            let mut hits = _mm_setzero_si128();
+            for &byte in bytes {
+                hits = _mm_or_si128(hits, _mm_cmpeq_epi8(chunk, _mm_set1_epi8(byte as i8)));
+            }
+            mask |= (_mm_movemask_epi8(hits) as u16 as u64) << (quarter * 16);
+        }
+        mask
+    };
+    BlockMasks {
+        structural: matching(b"{}[],:"),
+        whitespace: matching(b" \t\n\r"),
+        quote: matching(b"\""),
+        backslash: matching(b"\\"),
+    }
+}
+
+// Each bit becomes the XOR of itself and every bit below it, turning quote positions into a mask
+// that is set from an opening quote up to (not including) its closing quote.
+fn prefix_xor(mut mask: u64) -> u64 {
+    mask ^= mask << 1;
+    mask ^= mask << 2;
+    mask ^= mask << 4;
+    mask ^= mask << 8;
+    mask ^= mask << 16;
+    mask ^= mask << 32;
+    mask
+}
+
+// State carried from one block into the next.
+#[derive(Default)]
+struct Carry {
+    next_is_escaped: u64,
+    in_string: u64,
+    in_scalar: u64,
+}
+
+impl Carry {
+    // Marks the bytes escaped by a backslash: those after an odd-length run of backslashes.
+    fn escaped(&mut self, backslash: u64) -> u64 {
+        if backslash == 0 {
+            return std::mem::take(&mut self.next_is_escaped);
+        }
+        let potential_escape = backslash & !self.next_is_escaped;
+        let maybe_escaped_and_odd_bits = (potential_escape << 1) | ODD_BITS;
+        let even_series_codes_and_odd_bits =
+            maybe_escaped_and_odd_bits.wrapping_sub(potential_escape);
+        let escape_and_terminal_code = even_series_codes_and_odd_bits ^ ODD_BITS;
+        let escaped = escape_and_terminal_code ^ (backslash | self.next_is_escaped);
+        self.next_is_escaped = (escape_and_terminal_code & backslash) >> 63;
+        escaped
+    }
+
+    // Bits of the block where a token starts: structural characters and quotes outside strings,
+    // and the first byte of every run of other non-whitespace bytes.
+    fn token_starts(&mut self, masks: &BlockMasks) -> u64 {
+        let quote = masks.quote & !self.escaped(masks.backslash);
+        let in_string = prefix_xor(quote) ^ self.in_string;
+        self.in_string = ((in_string as i64) >> 63) as u64;
+
+        let outside = !(in_string | quote);
+        let scalar = outside & !(masks.structural | masks.whitespace);
+        let scalar_start = scalar & !((scalar << 1) | self.in_scalar);
+        self.in_scalar = scalar >> 63;
+
+        (masks.structural & outside) | quote | scalar_start
+    }
+}
+
+// Stage one: the offset of every token start, found 64 bytes at a time.
+#[cfg(target_arch = "x86_64")]
+fn structural_indices(buffer: &[u8], kernel: Kernel) -> Vec<u32> {
+    profile_block!("Structural scan");
+    // SAFETY: `detect` only hands out kernels whose features the CPU has.
+    unsafe {
+        match kernel {
+            Kernel::Avx2 => structural_indices_avx2(buffer),
+            Kernel::Sse2 => structural_indices_sse2(buffer),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn structural_indices(_buffer: &[u8], kernel: Kernel) -> Vec<u32> {
+    match kernel {}
+}
+
+// Compiled once per kernel so the mask computation inlines into the block loop.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn structural_indices_avx2(buffer: &[u8]) -> Vec<u32> {
+    scan_blocks(buffer, |block| unsafe { masks_avx2(block) })
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse2")]
+unsafe fn structural_indices_sse2(buffer: &[u8]) -> Vec<u32> {
+    scan_blocks(buffer, |block| unsafe { masks_sse2(block) })
+}
+
+#[inline(always)]
+fn scan_blocks(buffer: &[u8], masks: impl Fn(&[u8; 64]) -> BlockMasks) -> Vec<u32> {
+    let mut indices = Vec::with_capacity(buffer.len() / 4);
+    let mut carry = Carry::default();
+
+    let mut blocks = buffer.chunks_exact(64);
+    let mut base = 0;
+    let mut push_block = |block: &[u8; 64], indices: &mut Vec<u32>| {
+        let mut starts = carry.token_starts(&masks(block));
+        while starts != 0 {
+            indices.push(base + starts.trailing_zeros());
+            starts &= starts - 1;
+        }
+        base += 64;
+    };
+    for block in &mut blocks {
+        push_block(block.try_into().unwrap(), &mut indices);
+    }
+    let remainder = blocks.remainder();
+    if !remainder.is_empty() {
+        // Pad with whitespace, which never starts a token.
+        let mut block = [b' '; 64];
+        block[..remainder.len()].copy_from_slice(remainder);
+        push_block(&block, &mut indices);
+    }
+    indices
+}
+
+fn ends_run(byte: u8) -> bool {
+    matches!(
+        byte,
+        b'{' | b'}' | b'[' | b']' | b',' | b':' | b'"' | b' ' | b'\t' | b'\n' | b'\r'
+    )
+}
+
+// Offset of the first control character in string content that is not part of an escape.
+fn find_control_character(content: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    while i < content.len() {
+        match content[i] {
+            b'\\' => i += 2,
+            byte if byte < 0x20 => return Some(i),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+// Stage two: turns token starts into span tokens, lexing only the bytes of numbers and literals.
+fn build_tokens(
+    buffer: &[u8],
+    indices: &[u32],
+    options: &ParseOptions,
+) -> Result<Vec<SpanToken>, ParseError> {
+    profile_block!("Build tokens");
+    let mut tokens = Vec::with_capacity(indices.len());
+    let mut indices = indices.iter().map(|&index| index as usize);
+
+    while let Some(start) = indices.next() {
+        let kind = match buffer[start] {
+            b'{' => TokenKind::OpenBrace,
+            b'}' => TokenKind::CloseBrace,
+            b'[' => TokenKind::OpenBracket,
+            b']' => TokenKind::CloseBracket,
+            b',' => TokenKind::Comma,
+            b':' => TokenKind::Colon,
+            b'"' => {
+                // Nothing inside a string is indexed, so the next index is its closing quote.
+                let end = indices.next();
+                let content = &buffer[start + 1..end.unwrap_or(buffer.len())];
+                if options.strict && let Some(offset) = find_control_character(content) {
+                    return Err(ParseError::new(
+                        Position::locate(buffer, start + 1 + offset),
+                        describe_byte(content[offset]),
+                        "an escaped control character",
+                    ));
+                }
+                let Some(end) = end else {
+                    return Err(ParseError::new(
+                        Position::locate(buffer, buffer.len()),
+                        "end of input",
+                        "closing '\"' of string",
+                    ));
+                };
+                tokens.push(SpanToken {
+                    kind: if content.contains(&b'\\') {
+                        TokenKind::EscapedString
+                    } else {
+                        TokenKind::String
+                    },
+                    start: (start + 1) as u32,
+                    end: end as u32,
+                });
+                continue;
+            }
+            _ => {
+                // A run of non-structural bytes, lexed exactly as the scalar tokenizer would.
+                let mut i = start;
+                while i < buffer.len() && !ends_run(buffer[i]) {
+                    let (token, next) = scalar_token(buffer, i, options)?;
+                    tokens.extend(token);
+                    i = next;
+                }
+                continue;
+            }
+        };
+        tokens.push(SpanToken {
+            kind,
+            start: start as u32,
+            end: (start + 1) as u32,
+        });
+    }
+    Ok(tokens)
+}
+
+// Tokenizes like `span_lexer::tokenize`, finding token boundaries with SIMD bitmasks first. Falls
+// back to the scalar tokenizer when the CPU has no supported vector extension.
+pub fn tokenize_simd(buffer: &[u8], options: &ParseOptions) -> Result<Vec<SpanToken>, ParseError> {
+    let Some(kernel) = Kernel::detect() else {
+        return tokenize(buffer, options);
+    };
+    if buffer.len() > u32::MAX as usize {
+        return tokenize(buffer, options);
+    }
+    let indices = structural_indices(buffer, kernel);
+    build_tokens(buffer, &indices, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::DuplicateKeys;
+
+    fn spans(tokens: &[SpanToken]) -> Vec<(TokenKind, u32, u32)> {
+        tokens.iter().map(|token| (token.kind, token.start, token.end)).collect()
+    }
+
+    // The scalar tokenizer is the oracle: same tokens on success, same error otherwise.
+    fn assert_matches_scalar(input: &[u8], options: &ParseOptions) {
+        let expected = tokenize(input, options);
+        let actual = tokenize_simd(input, options);
+        match (expected, actual) {
+            (Ok(expected), Ok(actual)) => {
+                assert_eq!(spans(&expected), spans(&actual), "{}", String::from_utf8_lossy(input));
+            }
+            (Err(expected), Err(actual)) => {
+                assert_eq!(expected.to_string(), actual.to_string(), "{}", String::from_utf8_lossy(input));
+            }
+            (expected, actual) => panic!(
+                "{}: scalar {:?}, simd {:?}",
+                String::from_utf8_lossy(input),
+                expected.map(|tokens| spans(&tokens)),
+                actual.map(|tokens| spans(&tokens))
+            ),
+        }
+    }
+
+    const STRICT: ParseOptions = ParseOptions { strict: true, duplicate_keys: DuplicateKeys::LastWins };
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn every_kernel_matches_scalar_tokenizer() {
+        let input = br#"{"pairs":[{"x0":-1.5e3,"y0":2,"x1":0.25,"y1":true},[null,false,"a\"b\\"]]} "#.repeat(5);
+        let mut kernels = vec![Kernel::Sse2];
+        if Kernel::detect() == Some(Kernel::Avx2) {
+            kernels.push(Kernel::Avx2);
+        }
+        for kernel in kernels {
+            let indices = structural_indices(&input, kernel);
+            let tokens = build_tokens(&input, &indices, &STRICT).unwrap();
+            assert_eq!(spans(&tokens), spans(&tokenize(&input, &STRICT).unwrap()), "{kernel:?}");
+        }
+    }
+
+    #[test]
+    fn tokens_straddling_block_boundaries() {
+        let pieces: &[&[u8]] = &[
+            br#""plain""#,
+            br#""quote \" inside""#,
+            br#""ends in backslash \\""#,
+            br#""\\\" odd then quote""#,
+            br#""\\\\\\\\""#,
+            br#""\\\\\\\\\"\\""#,
+            "\"é😀\"".as_bytes(),
+            br#""{[,:]} inside""#,
+            b"-12345.678e-9",
+            b"true",
+            b"false",
+            b"null",
+            br#"{"k":[1,2]}"#,
+            // Unterminated string and a stray escape at the very end.
+            br#""open"#,
+            br#""open \"#,
+        ];
+        for piece in pieces {
+            // Slide the piece over two block boundaries, wrapped in a value and a key.
+            for padding in 0..130 {
+                let mut input = vec![b'['];
+                input.extend(std::iter::repeat_n(b' ', padding));
+                input.extend_from_slice(piece);
+                input.extend_from_slice(b",{\"");
+                input.extend_from_slice(&piece[piece.starts_with(b"\"") as usize..]);
+                input.extend_from_slice(b":0}]");
+                assert_matches_scalar(&input, &STRICT);
+                assert_matches_scalar(&input, &ParseOptions::default());
+                assert_matches_scalar(&input[..input.len() - padding.min(input.len())], &ParseOptions::default());
+            }
+        }
+    }
+
+    #[test]
+    fn long_generated_document() {
+        let mut input = String::from("{\"pairs\":[");
+        for i in 0..500 {
+            if i > 0 {
+                input.push(',');
+            }
+            let escapes = "\\\\".repeat(i % 7) + if i % 3 == 0 { "\\\"" } else { "" };
+            input.push_str(&format!(
+                "{{\"x0\":{},\"name\":\"p{}{}\",\"y0\":-{}.{}e{},\"ok\":{}}}",
+                i,
+                i,
+                escapes,
+                i * 7,
+                i % 10,
+                i % 5,
+                i % 2 == 0
+            ));
+        }
+        input.push_str("]}");
+        assert_matches_scalar(input.as_bytes(), &STRICT);
+        assert_matches_scalar(input.as_bytes(), &ParseOptions::default());
+    }
+
+    #[test]
+    fn lenient_stray_bytes_and_strict_errors() {
+        for input in [&b"[1, @, 2]"[..], b"[1 2 3]", b"[\"tab\there\"]", b"[tru]", b"[01]", b"[1.]"] {
+            for padding in [0, 60, 63, 64, 65] {
+                let mut padded = vec![b' '; padding];
+                padded.extend_from_slice(input);
+                assert_matches_scalar(&padded, &STRICT);
+                assert_matches_scalar(&padded, &ParseOptions::default());
+            }
+        }
+    }
+}